@@ -2,9 +2,7 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::{Command, exit};
 
-const SHADER_FILES: &[&str] = &[
-    "rasterzation.comp",
-];
+const SHADER_FILES: &[&str] = &["rasterzation.comp"];
 
 fn main() {
     let out_dir = PathBuf::from("./target/shaders/");
@@ -52,10 +50,11 @@ fn main() {
         let abs_path = out_dir.join(&spv_file).to_str().unwrap().replace('\\', "/");
         mod_content.push_str(&format!(
             "pub const {}: &[u32] = include_shader_code!(\"{}\");\n",
-            ident, "../.".to_string()+&abs_path
+            ident,
+            "../.".to_string() + &abs_path
         ));
     }
 
     fs::write(&mod_path, mod_content).unwrap();
     println!("cargo:rerun-if-changed=src/shaders");
-}
\ No newline at end of file
+}
@@ -6,24 +6,51 @@ import org.jspecify.annotations.Nullable;
 
 import java.lang.foreign.*;
 import java.lang.invoke.MethodHandle;
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.MethodType;
 import java.nio.file.Path;
 import java.util.ArrayList;
 import java.util.Collections;
 import java.util.List;
+import java.util.Map;
 
 public final class NativeContext {
+    public static final int JOB_RUNNING = 0;
+    public static final int JOB_COMPLETED = 1;
+    public static final int JOB_FAILED = 2;
+
     private static final MethodHandle CREATE_NATIVE_CONTEXT;
     private static final MethodHandle DESTROY_NATIVE_CONTEXT;
     private static final MethodHandle LOAD_EXTENSION;
+    private static final MethodHandle LOAD_EXTENSIONS;
     private static final MethodHandle INITIALIZE_EXTENSION;
     private static final MethodHandle INITIALIZE_EXTENSIONS;
     private static final MethodHandle DISABLE_EXTENSION;
     private static final MethodHandle UNLOAD_EXTENSION;
+    private static final MethodHandle UPGRADE_EXTENSION;
     private static final MethodHandle POP_ERROR;
     private static final MethodHandle ERROR_COUNT;
     private static final MethodHandle SET_ENABLED_VULKAN_FEATURES;
     private static final MethodHandle SET_ENABLED_VULKAN_EXTENSIONS;
+    private static final MethodHandle SET_LOCALE;
+    private static final MethodHandle LIST_EXTENSIONS;
+    private static final MethodHandle GET_EXTENSION_ICON;
+    private static final MethodHandle WATCH_EXTENSIONS;
+    private static final MethodHandle DISPATCH;
+    private static final MethodHandle DISPATCH_BACKGROUND;
+    private static final MethodHandle POLL_JOB;
+    private static final MethodHandle TICK;
+    private static final MethodHandle START_PROFILING;
+    private static final MethodHandle STOP_PROFILING;
+    private static final MethodHandle GET_EXTENSION_METRICS;
+    private static final MethodHandle RESET_EXTENSION_METRICS;
+    private static final MethodHandle LIST_CRASH_REPORTS;
+    private static final MethodHandle GET_SKIPPED_EXTENSIONS;
+    private static final MethodHandle REENABLE_EXTENSION;
+    private static final MethodHandle SET_JOB_CALLBACK;
+    private static final MethodHandle POLL_EXTENSION_RELOADS;
     private static final MethodHandle FREE_STRING;
+    private static final MethodHandle FREE_BYTES;
 
     static {
         try {
@@ -68,6 +95,13 @@ public final class NativeContext {
                             ValueLayout.ADDRESS, ValueLayout.ADDRESS)
             );
 
+            var loadExtsSymbol = lookup.find("ark_load_extensions").orElseThrow();
+            LOAD_EXTENSIONS = linker.downcallHandle(
+                    loadExtsSymbol,
+                    FunctionDescriptor.of(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS)
+            );
+
             var initExtSymbol = lookup.find("ark_initialize_extension").orElseThrow();
             INITIALIZE_EXTENSION = linker.downcallHandle(
                     initExtSymbol,
@@ -95,6 +129,13 @@ public final class NativeContext {
                             ValueLayout.ADDRESS)
             );
 
+            var upgradeExtSymbol = lookup.find("ark_upgrade_extension").orElseThrow();
+            UPGRADE_EXTENSION = linker.downcallHandle(
+                    upgradeExtSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS, ValueLayout.ADDRESS)
+            );
+
             var setFeaturesSymbol = lookup.find("ark_set_enabled_vulkan_features").orElseThrow();
             SET_ENABLED_VULKAN_FEATURES = linker.downcallHandle(
                     setFeaturesSymbol,
@@ -109,11 +150,131 @@ public final class NativeContext {
                             ValueLayout.ADDRESS)
             );
 
+            var setLocaleSymbol = lookup.find("ark_set_locale").orElseThrow();
+            SET_LOCALE = linker.downcallHandle(
+                    setLocaleSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS)
+            );
+
+            var listExtsSymbol = lookup.find("ark_list_extensions").orElseThrow();
+            LIST_EXTENSIONS = linker.downcallHandle(
+                    listExtsSymbol,
+                    FunctionDescriptor.of(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG)
+            );
+
+            var getIconSymbol = lookup.find("ark_get_extension_icon").orElseThrow();
+            GET_EXTENSION_ICON = linker.downcallHandle(
+                    getIconSymbol,
+                    FunctionDescriptor.of(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS, ValueLayout.JAVA_INT,
+                            ValueLayout.ADDRESS, ValueLayout.ADDRESS)
+            );
+
+            var watchExtsSymbol = lookup.find("ark_watch_extensions").orElseThrow();
+            WATCH_EXTENSIONS = linker.downcallHandle(
+                    watchExtsSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.JAVA_INT)
+            );
+
+            var pollReloadsSymbol = lookup.find("ark_poll_extension_reloads").orElseThrow();
+            POLL_EXTENSION_RELOADS = linker.downcallHandle(
+                    pollReloadsSymbol,
+                    FunctionDescriptor.of(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG)
+            );
+
+            var dispatchSymbol = lookup.find("ark_dispatch").orElseThrow();
+            DISPATCH = linker.downcallHandle(
+                    dispatchSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS)
+            );
+
+            var dispatchBgSymbol = lookup.find("ark_dispatch_background").orElseThrow();
+            DISPATCH_BACKGROUND = linker.downcallHandle(
+                    dispatchBgSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_LONG, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS)
+            );
+
+            var pollJobSymbol = lookup.find("ark_poll_job").orElseThrow();
+            POLL_JOB = linker.downcallHandle(
+                    pollJobSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.JAVA_LONG)
+            );
+
+            var tickSymbol = lookup.find("ark_tick").orElseThrow();
+            TICK = linker.downcallHandle(
+                    tickSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.JAVA_LONG)
+            );
+
+            var startProfilingSymbol = lookup.find("ark_start_profiling").orElseThrow();
+            START_PROFILING = linker.downcallHandle(
+                    startProfilingSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS)
+            );
+
+            var stopProfilingSymbol = lookup.find("ark_stop_profiling").orElseThrow();
+            STOP_PROFILING = linker.downcallHandle(
+                    stopProfilingSymbol,
+                    FunctionDescriptor.of(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS)
+            );
+
+            var getMetricsSymbol = lookup.find("ark_get_extension_metrics").orElseThrow();
+            GET_EXTENSION_METRICS = linker.downcallHandle(
+                    getMetricsSymbol,
+                    FunctionDescriptor.of(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG)
+            );
+
+            var resetMetricsSymbol = lookup.find("ark_reset_extension_metrics").orElseThrow();
+            RESET_EXTENSION_METRICS = linker.downcallHandle(
+                    resetMetricsSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS)
+            );
+
+            var listCrashReportsSymbol = lookup.find("ark_list_crash_reports").orElseThrow();
+            LIST_CRASH_REPORTS = linker.downcallHandle(
+                    listCrashReportsSymbol,
+                    FunctionDescriptor.of(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG)
+            );
+
+            var getSkippedSymbol = lookup.find("ark_get_skipped_extensions").orElseThrow();
+            GET_SKIPPED_EXTENSIONS = linker.downcallHandle(
+                    getSkippedSymbol,
+                    FunctionDescriptor.of(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG)
+            );
+
+            var reenableSymbol = lookup.find("ark_reenable_extension").orElseThrow();
+            REENABLE_EXTENSION = linker.downcallHandle(
+                    reenableSymbol,
+                    FunctionDescriptor.of(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG,
+                            ValueLayout.ADDRESS)
+            );
+
+            var setJobCallbackSymbol = lookup.find("ark_set_job_callback").orElseThrow();
+            SET_JOB_CALLBACK = linker.downcallHandle(
+                    setJobCallbackSymbol,
+                    FunctionDescriptor.ofVoid(ValueLayout.JAVA_LONG, ValueLayout.ADDRESS)
+            );
+
             var freeStringSymbol = lookup.find("ark_free_string").orElseThrow();
             FREE_STRING = linker.downcallHandle(
                     freeStringSymbol,
                     FunctionDescriptor.ofVoid(ValueLayout.ADDRESS)
             );
+
+            var freeBytesSymbol = lookup.find("ark_free_bytes").orElseThrow();
+            FREE_BYTES = linker.downcallHandle(
+                    freeBytesSymbol,
+                    FunctionDescriptor.ofVoid(ValueLayout.ADDRESS, ValueLayout.JAVA_LONG)
+            );
         } catch (Exception e) {
             throw new ExceptionInInitializerError(e);
         }
@@ -229,7 +390,41 @@ public final class NativeContext {
         }
     }
 
-    /// Initializes a specific loaded extension by its manifest id.
+    /// Loads several extensions at once: packages are compiled in parallel and instantiated
+    /// so that each extension is loaded after the extensions it depends on.
+    ///
+    /// @param requests zip file names mapped to their WASI feature strings (null for none)
+    /// @return a JSON array with one `{file_name, identifier, error}` result per request,
+    /// or null on failure
+    public @Nullable String loadExtensions(@NonNull Map<String, @Nullable List<String>> requests) {
+        var sb = new StringBuilder("[");
+        for (var request : requests.entrySet()) {
+            if (sb.length() > 1) sb.append(',');
+            var features = toJsonArray(request.getValue());
+            sb.append("{\"file_name\":\"");
+            sb.append(request.getKey().replace("\\", "\\\\").replace("\"", "\\\""));
+            sb.append("\",\"wasi_features\":");
+            sb.append(features != null ? features : "[]");
+            sb.append('}');
+        }
+        sb.append(']');
+        try (var arena = Arena.ofConfined()) {
+            var requestsSeg = arena.allocateFrom(sb.toString());
+            var jsonPtr = (MemorySegment) LOAD_EXTENSIONS.invokeExact(this.address, requestsSeg);
+            if (MemorySegment.NULL.equals(jsonPtr)) {
+                return null;
+            }
+            var json = jsonPtr.reinterpret(Long.MAX_VALUE).getString(0);
+            FREE_STRING.invokeExact(jsonPtr);
+            return json;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to load extensions {}", requests.keySet(), t);
+            return null;
+        }
+    }
+
+    /// Initializes a specific loaded extension by its `namespace:id`, or by its bare
+    /// manifest id when no other namespace uses the same id.
     ///
     /// @return true on success
     public boolean initializeExtension(@NonNull String id) {
@@ -283,6 +478,22 @@ public final class NativeContext {
         }
     }
 
+    /// Replaces a loaded extension with a higher version of the same `namespace:id` from
+    /// `fileName` in the extension folder, calling its `migrate_function` if it declares one.
+    /// On failure the previous version is re-initialized and stays loaded.
+    /// @return true on success
+    public boolean upgradeExtension(@NonNull String id, @NonNull String fileName) {
+        try (var arena = Arena.ofConfined()) {
+            var idSeg = arena.allocateFrom(id);
+            var fileSeg = arena.allocateFrom(fileName);
+            int rc = (int) UPGRADE_EXTENSION.invokeExact(this.address, idSeg, fileSeg);
+            return rc == 0;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to upgrade extension '{}'", id, t);
+            return false;
+        }
+    }
+
     /// Sets the enabled Vulkan feature names on the native side, as a JSON array.
     /// This populates the sets queried by WASM extensions via check_vulkan_feature().
     /// @return true on success
@@ -313,6 +524,293 @@ public final class NativeContext {
         }
     }
 
+    /// Sets the locale used for extension names, descriptions and error messages,
+    /// e.g. `en_us`. Pass null to reset to `en_us`.
+    /// @return true on success
+    public boolean setLocale(@Nullable String locale) {
+        try (var arena = Arena.ofConfined()) {
+            var localeSeg = locale != null ? arena.allocateFrom(locale) : MemorySegment.NULL;
+            int rc = (int) SET_LOCALE.invokeExact(this.address, localeSeg);
+            return rc == 0;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to set locale '{}'", locale, t);
+            return false;
+        }
+    }
+
+    /// Lists the loaded extensions as a JSON array of objects with `id`, `namespace`,
+    /// `version`, `name`, `description`, `hash` and `verified`, localized to the current locale.
+    /// @return the JSON string, or null on failure
+    public @Nullable String listExtensions() {
+        try {
+            var jsonPtr = (MemorySegment) LIST_EXTENSIONS.invokeExact(this.address);
+            if (MemorySegment.NULL.equals(jsonPtr)) {
+                return null;
+            }
+            var json = jsonPtr.reinterpret(Long.MAX_VALUE).getString(0);
+            FREE_STRING.invokeExact(jsonPtr);
+            return json;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to list extensions", t);
+            return null;
+        }
+    }
+
+    /// Calls the handler registered for `trigger` on the current thread.
+    /// @return true on success
+    public boolean dispatch(@NonNull String trigger) {
+        try (var arena = Arena.ofConfined()) {
+            var triggerSeg = arena.allocateFrom(trigger);
+            int rc = (int) DISPATCH.invokeExact(this.address, triggerSeg);
+            return rc == 0;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to dispatch '{}'", trigger, t);
+            return false;
+        }
+    }
+
+    /// Runs the handler registered for `trigger` as a background job on the native worker
+    /// pool. The handler must be marked `background` in its extension's manifest.
+    /// @return the job id to pass to [#pollJob(long)], or -1 on failure
+    public long dispatchBackground(@NonNull String trigger) {
+        try (var arena = Arena.ofConfined()) {
+            var triggerSeg = arena.allocateFrom(trigger);
+            return (long) DISPATCH_BACKGROUND.invokeExact(this.address, triggerSeg);
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to dispatch '{}' in the background", trigger, t);
+            return -1;
+        }
+    }
+
+    /// Returns the status of a background job: [#JOB_RUNNING], [#JOB_COMPLETED] or
+    /// [#JOB_FAILED] (the error can then be popped), or -1 for an unknown job.
+    /// Finished jobs are forgotten once polled.
+    public int pollJob(long job) {
+        try {
+            return (int) POLL_JOB.invokeExact(this.address, job);
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to poll job {}", job, t);
+            return -1;
+        }
+    }
+
+    /// Delivers queued extension bus messages, then advances the extension scheduler to `now`
+    /// and runs the due timers and tick callbacks, all on the current thread. `now` is in
+    /// milliseconds of any monotonic clock, used consistently across calls.
+    /// @return true if every callback succeeded; failed callbacks are cancelled and their
+    /// errors can be popped
+    public boolean tick(long now) {
+        try {
+            int rc = (int) TICK.invokeExact(this.address, now);
+            return rc == 0;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to tick extensions", t);
+            return false;
+        }
+    }
+
+    /// Starts recording a guest profile of an extension. Profiling must be enabled under
+    /// `profiling` in the extension folder's `host.json`.
+    /// @return true on success
+    public boolean startProfiling(@NonNull String id) {
+        try (var arena = Arena.ofConfined()) {
+            var idSeg = arena.allocateFrom(id);
+            int rc = (int) START_PROFILING.invokeExact(this.address, idSeg);
+            return rc == 0;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to start profiling extension '{}'", id, t);
+            return false;
+        }
+    }
+
+    /// Stops profiling an extension and writes the profile as JSON that can be opened in the
+    /// Firefox profiler.
+    /// @return the path of the profile file, or null on failure
+    public @Nullable String stopProfiling(@NonNull String id) {
+        try (var arena = Arena.ofConfined()) {
+            var idSeg = arena.allocateFrom(id);
+            var pathPtr = (MemorySegment) STOP_PROFILING.invokeExact(this.address, idSeg);
+            if (MemorySegment.NULL.equals(pathPtr)) {
+                return null;
+            }
+            var path = pathPtr.reinterpret(Long.MAX_VALUE).getString(0);
+            FREE_STRING.invokeExact(pathPtr);
+            return path;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to stop profiling extension '{}'", id, t);
+            return null;
+        }
+    }
+
+    /// Returns the call metrics of the loaded extensions as a JSON array of objects with
+    /// `identifier`, `total`, `triggers` (keyed by trigger name) and `memory_high_water`.
+    /// Call metrics hold `calls`, `total_micros`, `max_micros`, `traps` and `fuel`; fuel is only
+    /// counted when `metrics.fuel` is enabled in `host.json`.
+    /// @return the JSON string, or null on failure
+    public @Nullable String getExtensionMetrics() {
+        try {
+            var jsonPtr = (MemorySegment) GET_EXTENSION_METRICS.invokeExact(this.address);
+            if (MemorySegment.NULL.equals(jsonPtr)) {
+                return null;
+            }
+            var json = jsonPtr.reinterpret(Long.MAX_VALUE).getString(0);
+            FREE_STRING.invokeExact(jsonPtr);
+            return json;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to get extension metrics", t);
+            return null;
+        }
+    }
+
+    /// Resets the metrics of an extension, or of every loaded extension if `id` is null.
+    /// @return true on success
+    public boolean resetExtensionMetrics(@Nullable String id) {
+        try (var arena = Arena.ofConfined()) {
+            var idSeg = id != null ? arena.allocateFrom(id) : MemorySegment.NULL;
+            int rc = (int) RESET_EXTENSION_METRICS.invokeExact(this.address, idSeg);
+            return rc == 0;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to reset extension metrics", t);
+            return false;
+        }
+    }
+
+    /// Lists the crash reports written when calls into extensions failed, newest first, as a
+    /// JSON array of objects with `path`, `identifier`, `trigger`, `timestamp` (milliseconds
+    /// since the epoch) and `error`. Reports are configured under `crash_reports` in
+    /// `host.json`.
+    /// @return the JSON string, or null on failure
+    public @Nullable String listCrashReports() {
+        try {
+            var jsonPtr = (MemorySegment) LIST_CRASH_REPORTS.invokeExact(this.address);
+            if (MemorySegment.NULL.equals(jsonPtr)) {
+                return null;
+            }
+            var json = jsonPtr.reinterpret(Long.MAX_VALUE).getString(0);
+            FREE_STRING.invokeExact(jsonPtr);
+            return json;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to list crash reports", t);
+            return null;
+        }
+    }
+
+    /// Returns the extensions skipped because a previous run crashed while they were
+    /// initializing, as a JSON object with `all` (every extension is skipped) and `suspects`
+    /// (the extensions that were initializing). Loading a skipped extension fails until it is
+    /// re-enabled with [#reenableExtension(String)].
+    /// @return the JSON string, or null on failure
+    public @Nullable String getSkippedExtensions() {
+        try {
+            var jsonPtr = (MemorySegment) GET_SKIPPED_EXTENSIONS.invokeExact(this.address);
+            if (MemorySegment.NULL.equals(jsonPtr)) {
+                return null;
+            }
+            var json = jsonPtr.reinterpret(Long.MAX_VALUE).getString(0);
+            FREE_STRING.invokeExact(jsonPtr);
+            return json;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to get skipped extensions", t);
+            return null;
+        }
+    }
+
+    /// Lets an extension skipped in safe mode load again, or every skipped extension if `id`
+    /// is null.
+    /// @return true on success
+    public boolean reenableExtension(@Nullable String id) {
+        try (var arena = Arena.ofConfined()) {
+            var idSeg = id != null ? arena.allocateFrom(id) : MemorySegment.NULL;
+            int rc = (int) REENABLE_EXTENSION.invokeExact(this.address, idSeg);
+            return rc == 0;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to re-enable extension '{}'", id, t);
+            return false;
+        }
+    }
+
+    /// Sets the listener notified when a background job finishes, or removes it with null.
+    /// The listener runs on a native worker thread and must not throw.
+    /// @return true on success
+    public boolean setJobListener(@Nullable JobListener listener) {
+        try {
+            var stub = MemorySegment.NULL;
+            if (listener != null) {
+                var target = MethodHandles.lookup()
+                        .findVirtual(JobListener.class, "onJobFinished",
+                                MethodType.methodType(void.class, long.class, int.class))
+                        .bindTo(listener);
+                stub = Linker.nativeLinker().upcallStub(target,
+                        FunctionDescriptor.ofVoid(ValueLayout.JAVA_LONG, ValueLayout.JAVA_INT),
+                        Arena.global());
+            }
+            SET_JOB_CALLBACK.invokeExact(this.address, stub);
+            return true;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to set job listener", t);
+            return false;
+        }
+    }
+
+    /// Starts or stops the native watcher on the extension folder.
+    /// Changes it sees are applied by [#pollExtensionReloads()].
+    /// @return true on success
+    public boolean watchExtensions(boolean enabled) {
+        try {
+            int rc = (int) WATCH_EXTENSIONS.invokeExact(this.address, enabled ? 1 : 0);
+            return rc == 0;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to {} watching extensions", enabled ? "start" : "stop", t);
+            return false;
+        }
+    }
+
+    /// Reloads extensions whose packages changed or were removed since the last call.
+    /// Packages no extension was loaded from are reported as `added` and left for the caller
+    /// to load with [#loadExtension(String, List)].
+    /// Call it from the render thread, e.g. once per frame.
+    /// @return a JSON array of events with `file_name`, `kind` (`added`, `reloaded`,
+    /// `unloaded` or `failed`), `identifier` and `error`, or null on failure
+    public @Nullable String pollExtensionReloads() {
+        try {
+            var jsonPtr = (MemorySegment) POLL_EXTENSION_RELOADS.invokeExact(this.address);
+            if (MemorySegment.NULL.equals(jsonPtr)) {
+                return null;
+            }
+            var json = jsonPtr.reinterpret(Long.MAX_VALUE).getString(0);
+            FREE_STRING.invokeExact(jsonPtr);
+            return json;
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to poll extension reloads", t);
+            return null;
+        }
+    }
+
+    /// Decodes an extension's icon, scaled to fit a `size`x`size` square.
+    /// Pass 0 to keep the icon's original size; sizes above 1024 are rejected.
+    /// @return the icon as RGBA bytes, or null if the extension has no usable icon
+    public @Nullable ExtensionIcon getExtensionIcon(@NonNull String id, int size) {
+        try (var arena = Arena.ofConfined()) {
+            var idSeg = arena.allocateFrom(id);
+            var widthSeg = arena.allocate(ValueLayout.JAVA_INT);
+            var heightSeg = arena.allocate(ValueLayout.JAVA_INT);
+            var pixelsPtr = (MemorySegment) GET_EXTENSION_ICON.invokeExact(
+                    this.address, idSeg, size, widthSeg, heightSeg);
+            if (MemorySegment.NULL.equals(pixelsPtr)) {
+                return null;
+            }
+            int width = widthSeg.get(ValueLayout.JAVA_INT, 0);
+            int height = heightSeg.get(ValueLayout.JAVA_INT, 0);
+            long len = (long) width * height * 4;
+            var rgba = pixelsPtr.reinterpret(len).toArray(ValueLayout.JAVA_BYTE);
+            FREE_BYTES.invokeExact(pixelsPtr, len);
+            return new ExtensionIcon(width, height, rgba);
+        } catch (Throwable t) {
+            Ark.LOGGER.error("Failed to get icon of extension '{}'", id, t);
+            return null;
+        }
+    }
+
     public long getAddress() {
         return this.address;
     }
@@ -321,4 +819,14 @@ public final class NativeContext {
     public String toString() {
         return Long.toHexString(this.getAddress());
     }
+
+    /// An extension icon decoded on the native side, as 8-bit RGBA pixels row by row.
+    public record ExtensionIcon(int width, int height, byte[] rgba) {
+    }
+
+    /// Receives background job completions on a native worker thread.
+    @FunctionalInterface
+    public interface JobListener {
+        void onJobFinished(long job, int status);
+    }
 }
@@ -18,6 +18,12 @@ public class ExtensionManifest {
     public String entrypoint = "script";
     public String entry_function = "initialize";
     public String close_function;
+    public String migrate_function;
+    public List<String> precompiled = List.of();
+    public List<String> dependencies = List.of();
+    public List<String> provides = List.of();
+    public Map<String, String> handlers = new HashMap<>(0);
+    public BusPermissions bus = new BusPermissions();
     public RuntimeArgs runtime = new RuntimeArgs();
     public String name;
     public String version = "";
@@ -37,6 +43,11 @@ public class ExtensionManifest {
         }
     }
 
+    public static class BusPermissions {
+        public List<String> publish = List.of();
+        public List<String> subscribe = List.of();
+    }
+
     public static class RuntimeArgs {
         public String required_vulkan_version = "1.2.0";
         public String required_ark_version = "0.1.0";
@@ -45,6 +56,8 @@ public class ExtensionManifest {
         public List<String> required_vulkan_features = List.of();
         public List<String> optional_vulkan_features = List.of();
         public List<String> optional_wasi_features = List.of();
+        public Long max_memory;
+        public boolean debug = false;
     }
 
     public static class ValueOrList {
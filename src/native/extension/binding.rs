@@ -3,41 +3,130 @@ use wasmtime::{
     component::{Access, Linker, TypedFunc, bindgen},
 };
 
-use crate::extension::{binding::ark::core::logging::{Level}, wasm::ExtensionContext};
+use crate::extension::{
+    binding::ark::core::{bus::Payload, logging::Level},
+    bus::BusHandler,
+    compat,
+    i18n::DEFAULT_LOCALE,
+    wasm::{ExtensionContext, ExtensionInstance, RegisteredFunc, Registration},
+};
+
+pub mod v0_1;
 
 bindgen!({
     world: "core",
     anyhow: true,
     imports: {
-        "register": store,
+        "ark:core/host.register": store,
+        "ark:core/scheduler.after": store,
+        "ark:core/scheduler.every": store,
+        "ark:core/scheduler.on-tick": store,
+        "ark:core/bus.subscribe": store,
     },
 });
 
+/// Versions of the `ark:core` world the host can link against. Every version is added to
+/// the same linker; older ones are served through the shims in their own module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldVersion {
+    V0_1,
+    V0_2,
+}
+
+impl WorldVersion {
+    pub const LATEST: WorldVersion = WorldVersion::V0_2;
+
+    pub fn version(&self) -> &'static str {
+        match self {
+            WorldVersion::V0_1 => "0.1.0",
+            WorldVersion::V0_2 => "0.2.0",
+        }
+    }
+
+    /// Interfaces of the world, without their version suffix.
+    pub fn interfaces(&self) -> &'static [&'static str] {
+        match self {
+            WorldVersion::V0_1 => &["ark:core/logging"],
+            WorldVersion::V0_2 => &[
+                "ark:core/bus",
+                "ark:core/host",
+                "ark:core/i18n",
+                "ark:core/logging",
+                "ark:core/package",
+                "ark:core/scheduler",
+            ],
+        }
+    }
+
+    /// Functions imported directly by the world, which carry no version in their name.
+    pub fn functions(&self) -> &'static [&'static str] {
+        match self {
+            WorldVersion::V0_1 => &["register", "check-vulkan-feature", "check-vulkan-extension"],
+            WorldVersion::V0_2 => &[],
+        }
+    }
+
+    /// Finds the world version whose package version is semver-compatible with `version`.
+    pub fn from_version(version: &str) -> Option<WorldVersion> {
+        [WorldVersion::V0_1, WorldVersion::V0_2]
+            .into_iter()
+            .find(|world| compat::is_semver_compatible(version, world.version()))
+    }
+}
+
 pub(crate) fn add_to_linker(linker: &mut Linker<ExtensionContext>) -> Result<(), wasmtime::Error> {
-    ark::core::logging::add_to_linker::<ExtensionContext, ExtensionContext>(linker, |data:&mut ExtensionContext| data)?;
-    Core::add_to_linker::<ExtensionContext, ExtensionContext>(linker, |data:&mut ExtensionContext| data)?;
+    ark::core::host::add_to_linker::<ExtensionContext, ExtensionContext>(
+        linker,
+        |data: &mut ExtensionContext| data,
+    )?;
+    ark::core::i18n::add_to_linker::<ExtensionContext, ExtensionContext>(
+        linker,
+        |data: &mut ExtensionContext| data,
+    )?;
+    ark::core::logging::add_to_linker::<ExtensionContext, ExtensionContext>(
+        linker,
+        |data: &mut ExtensionContext| data,
+    )?;
+    ark::core::package::add_to_linker::<ExtensionContext, ExtensionContext>(
+        linker,
+        |data: &mut ExtensionContext| data,
+    )?;
+    ark::core::bus::add_to_linker::<ExtensionContext, ExtensionContext>(
+        linker,
+        |data: &mut ExtensionContext| data,
+    )?;
+    ark::core::scheduler::add_to_linker::<ExtensionContext, ExtensionContext>(
+        linker,
+        |data: &mut ExtensionContext| data,
+    )?;
+    v0_1::add_to_linker(linker)?;
     Ok(())
 }
 
 impl ark::core::logging::Host for ExtensionContext {
     fn trace(&mut self, message: String) {
+        self.recent_logs.push("TRACE", &message);
         log::trace!("[ark-ext-{}] {}", self.package.manifest.id, message);
     }
 
     fn debug(&mut self, message: String) {
-        log::debug!("[ark-ext-{}] {}", self.package.manifest.id, message)
+        self.recent_logs.push("DEBUG", &message);
+        log::debug!("[ark-ext-{}] {}", self.package.manifest.id, message);
     }
 
     fn info(&mut self, message: String) {
-        log::info!("[ark-ext-{}] {}", self.package.manifest.id, message)
+        self.recent_logs.push("INFO", &message);
+        log::info!("[ark-ext-{}] {}", self.package.manifest.id, message);
     }
 
     fn warn(&mut self, message: String) {
-        log::warn!("[ark-ext-{}] {}", self.package.manifest.id, message)
+        self.recent_logs.push("WARN", &message);
+        log::warn!("[ark-ext-{}] {}", self.package.manifest.id, message);
     }
 
     fn error(&mut self, message: String) {
-        log::error!("[ark-ext-{}] {}", self.package.manifest.id, message)
+        self.recent_logs.push("ERROR", &message);
+        log::error!("[ark-ext-{}] {}", self.package.manifest.id, message);
     }
 
     fn log(&mut self, level: Level, message: String) {
@@ -50,7 +139,7 @@ impl ark::core::logging::Host for ExtensionContext {
         }
     }
 
-    fn is_enabled(&mut self,level: Level,) -> bool {
+    fn is_enabled(&mut self, level: Level) -> bool {
         match level {
             Level::Trace => log::log_enabled!(log::Level::Trace),
             Level::Debug => log::log_enabled!(log::Level::Debug),
@@ -61,31 +150,92 @@ impl ark::core::logging::Host for ExtensionContext {
     }
 }
 
-impl CoreImportsWithStore for ExtensionContext {
+impl ark::core::package::Host for ExtensionContext {
+    fn list_files(&mut self) -> Vec<String> {
+        let mut files: Vec<String> = self
+            .package
+            .files
+            .keys()
+            .filter(|name| !name.ends_with('/'))
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn read_file(&mut self, path: String) -> Result<Vec<u8>, String> {
+        self.package
+            .files
+            .get(&path)
+            .map(|data| data.to_vec())
+            .ok_or(format!("File not found in package: {}", path))
+    }
+
+    fn file_size(&mut self, path: String) -> Result<u64, String> {
+        self.package
+            .files
+            .get(&path)
+            .map(|data| data.len() as u64)
+            .ok_or(format!("File not found in package: {}", path))
+    }
+}
+
+impl ark::core::i18n::Host for ExtensionContext {
+    fn current_locale(&mut self) -> String {
+        self.locale
+            .lock()
+            .map_or(DEFAULT_LOCALE.to_string(), |locale| locale.clone())
+    }
+
+    fn translate(&mut self, key: String) -> Option<String> {
+        let locale = self.current_locale();
+        self.package
+            .translations
+            .translate(&locale, &key)
+            .map(str::to_string)
+    }
+}
+
+/// Looks up the calling extension's exported nullary `function` as a handler it owns.
+fn handler<T>(
+    host: &mut Access<'_, T, ExtensionContext>,
+    function: &str,
+) -> Result<Registration, String> {
+    let data = host.get();
+    let owner = data.identifier.unversioned();
+    let store_id = data.store_id;
+    let affinity = data.package.manifest.handler_affinity(function);
+    let Some(ExtensionInstance::Component(instance)) = data.instance else {
+        return Err("Instance not found".to_string());
+    };
+    let fun: TypedFunc<(), ()> = instance
+        .get_typed_func(host.as_context_mut(), function)
+        .map_err(|err| err.to_string())?;
+    Ok(Registration {
+        func: RegisteredFunc::Component(fun),
+        owner,
+        store_id,
+        affinity,
+    })
+}
+
+impl ark::core::host::HostWithStore for ExtensionContext {
     fn register<T>(
         mut host: Access<'_, T, Self>,
         trigger: String,
         function: String,
     ) -> Result<(), String> {
-        let data = host.get();
-        let id = data.package.manifest.id.clone();
-        let registry = data.public_registry.clone();
-        let instance = data
-            .instance
-            .ok_or(wasmtime::Error::msg("Instance not found"))
-            .map_err(|err| err.to_string())?;
-        let fun: TypedFunc<(), ()> = instance
-            .get_typed_func(host.as_context_mut(), function)
-            .map_err(|err| err.to_string())?;
-        registry
+        let registration = handler(&mut host, &function)?;
+        host.get()
+            .public_registry
             .lock()
             .map_err(|err| err.to_string())?
-            .insert(trigger, (fun, id));
+            .insert(trigger, registration);
         Ok(())
     }
 }
 
-impl CoreImports for ExtensionContext {
+impl ark::core::host::Host for ExtensionContext {
     fn check_vulkan_feature(&mut self, feature: String) -> bool {
         self.enabled_vulkan_features
             .lock()
@@ -98,3 +248,85 @@ impl CoreImports for ExtensionContext {
             .is_ok_and(|s| s.contains(&extension))
     }
 }
+
+impl ark::core::scheduler::HostWithStore for ExtensionContext {
+    fn after<T>(mut host: Access<'_, T, Self>, ms: u64, function: String) -> Result<u64, String> {
+        let registration = handler(&mut host, &function)?;
+        let mut scheduler = host.get().scheduler.lock().map_err(|err| err.to_string())?;
+        Ok(scheduler.after(ms, registration))
+    }
+
+    fn every<T>(mut host: Access<'_, T, Self>, ms: u64, function: String) -> Result<u64, String> {
+        let registration = handler(&mut host, &function)?;
+        let mut scheduler = host.get().scheduler.lock().map_err(|err| err.to_string())?;
+        Ok(scheduler.every(ms, registration))
+    }
+
+    fn on_tick<T>(mut host: Access<'_, T, Self>, function: String) -> Result<u64, String> {
+        let registration = handler(&mut host, &function)?;
+        let mut scheduler = host.get().scheduler.lock().map_err(|err| err.to_string())?;
+        Ok(scheduler.on_tick(registration))
+    }
+}
+
+impl ark::core::scheduler::Host for ExtensionContext {
+    fn cancel(&mut self, handle: u64) -> bool {
+        let owner = self.identifier.unversioned();
+        self.scheduler
+            .lock()
+            .is_ok_and(|mut scheduler| scheduler.cancel(&owner, handle))
+    }
+}
+
+impl ark::core::bus::HostWithStore for ExtensionContext {
+    fn subscribe<T>(
+        mut host: Access<'_, T, Self>,
+        channel: String,
+        function: String,
+    ) -> Result<(), String> {
+        let data = host.get();
+        if !data.package.manifest.bus.may_subscribe(&channel) {
+            return Err(format!(
+                "Not permitted to subscribe to channel '{}'",
+                channel
+            ));
+        }
+        let owner = data.identifier.unversioned();
+        let store_id = data.store_id;
+        let Some(ExtensionInstance::Component(instance)) = data.instance else {
+            return Err("Instance not found".to_string());
+        };
+        let handler: BusHandler = instance
+            .get_typed_func(host.as_context_mut(), function)
+            .map_err(|err| err.to_string())?;
+        host.get()
+            .bus
+            .lock()
+            .map_err(|err| err.to_string())?
+            .subscribe(channel, owner, store_id, handler);
+        Ok(())
+    }
+}
+
+impl ark::core::bus::Host for ExtensionContext {
+    fn publish(&mut self, channel: String, payload: Payload) -> Result<(), String> {
+        if !self.package.manifest.bus.may_publish(&channel) {
+            return Err(format!("Not permitted to publish to channel '{}'", channel));
+        }
+        if let Payload::Json(json) = &payload {
+            serde_json::from_str::<serde_json::Value>(json)
+                .map_err(|err| format!("Invalid JSON payload: {}", err))?;
+        }
+        self.bus
+            .lock()
+            .map_err(|err| err.to_string())?
+            .publish(channel, payload)
+    }
+
+    fn unsubscribe(&mut self, channel: String) -> bool {
+        let owner = self.identifier.unversioned();
+        self.bus
+            .lock()
+            .is_ok_and(|mut bus| bus.unsubscribe(&channel, &owner))
+    }
+}
@@ -0,0 +1,88 @@
+use wasmtime::component::{Access, Linker, bindgen};
+
+use crate::extension::{binding as latest, wasm::ExtensionContext};
+
+bindgen!({
+    world: "core",
+    path: "wit/legacy/0.1.0",
+    anyhow: true,
+    imports: {
+        "register": store,
+    },
+});
+
+/// Adds the frozen `ark:core@0.1.0` world, served by delegating to the latest host traits.
+pub(crate) fn add_to_linker(linker: &mut Linker<ExtensionContext>) -> Result<(), wasmtime::Error> {
+    ark::core::logging::add_to_linker::<ExtensionContext, ExtensionContext>(
+        linker,
+        |data: &mut ExtensionContext| data,
+    )?;
+    Core::add_to_linker::<ExtensionContext, ExtensionContext>(
+        linker,
+        |data: &mut ExtensionContext| data,
+    )?;
+    Ok(())
+}
+
+fn to_latest_level(level: ark::core::logging::Level) -> latest::ark::core::logging::Level {
+    use ark::core::logging::Level;
+    match level {
+        Level::Trace => latest::ark::core::logging::Level::Trace,
+        Level::Debug => latest::ark::core::logging::Level::Debug,
+        Level::Info => latest::ark::core::logging::Level::Info,
+        Level::Warn => latest::ark::core::logging::Level::Warn,
+        Level::Error => latest::ark::core::logging::Level::Error,
+    }
+}
+
+impl ark::core::logging::Host for ExtensionContext {
+    fn trace(&mut self, message: String) {
+        latest::ark::core::logging::Host::trace(self, message)
+    }
+
+    fn debug(&mut self, message: String) {
+        latest::ark::core::logging::Host::debug(self, message)
+    }
+
+    fn info(&mut self, message: String) {
+        latest::ark::core::logging::Host::info(self, message)
+    }
+
+    fn warn(&mut self, message: String) {
+        latest::ark::core::logging::Host::warn(self, message)
+    }
+
+    fn error(&mut self, message: String) {
+        latest::ark::core::logging::Host::error(self, message)
+    }
+
+    fn log(&mut self, level: ark::core::logging::Level, message: String) {
+        latest::ark::core::logging::Host::log(self, to_latest_level(level), message)
+    }
+
+    fn is_enabled(&mut self, level: ark::core::logging::Level) -> bool {
+        latest::ark::core::logging::Host::is_enabled(self, to_latest_level(level))
+    }
+}
+
+impl CoreImportsWithStore for ExtensionContext {
+    fn register<T>(
+        host: Access<'_, T, Self>,
+        trigger: String,
+        function: String,
+    ) -> Result<(), String> {
+        <ExtensionContext as latest::ark::core::host::HostWithStore>::register(
+            host, trigger, function,
+        )
+    }
+}
+
+impl CoreImports for ExtensionContext {
+    fn check_vulkan_feature(&mut self, feature: String) -> bool {
+        latest::ark::core::host::Host::check_vulkan_feature(self, feature)
+    }
+
+    fn check_vulkan_extension(&mut self, extension: String) -> bool {
+        latest::ark::core::host::Host::check_vulkan_extension(self, extension)
+    }
+}
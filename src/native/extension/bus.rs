@@ -0,0 +1,224 @@
+use std::collections::{HashMap, VecDeque};
+
+use wasmtime::component::TypedFunc;
+
+use crate::extension::{binding::ark::core::bus::Payload, package::ExtensionIdentifier};
+
+/// Messages queued and not yet delivered; publishing fails once the queue is this long.
+pub static MAX_QUEUED_MESSAGES: usize = 4096;
+/// Largest payload, in bytes, a single message may carry.
+pub static MAX_PAYLOAD_SIZE: usize = 1024 * 1024;
+
+/// Handler an extension subscribed to a channel with, taking the channel and the payload.
+pub type BusHandler = TypedFunc<(String, Payload), ()>;
+
+struct Subscription {
+    owner: ExtensionIdentifier,
+    /// Store `handler` was looked up in, see
+    /// [`ExtensionContext::store_id`](crate::extension::wasm::ExtensionContext::store_id).
+    store_id: u64,
+    handler: BusHandler,
+}
+
+struct Message {
+    channel: String,
+    payload: Payload,
+}
+
+/// A message to hand to one subscriber.
+pub struct Delivery {
+    pub owner: ExtensionIdentifier,
+    pub store_id: u64,
+    pub handler: BusHandler,
+    pub channel: String,
+    pub payload: Payload,
+}
+
+/// Channels extensions publish to and subscribe to. Published messages are only queued;
+/// the runtime delivers them from its tick, so a publisher never calls into a subscriber.
+#[derive(Default)]
+pub struct Bus {
+    subscriptions: HashMap<String, Vec<Subscription>>,
+    queue: VecDeque<Message>,
+}
+
+impl Bus {
+    pub fn publish(&mut self, channel: String, payload: Payload) -> Result<(), String> {
+        let size = match &payload {
+            Payload::Bytes(bytes) => bytes.len(),
+            Payload::Json(json) => json.len(),
+        };
+        if size > MAX_PAYLOAD_SIZE {
+            return Err(format!(
+                "Payload of {} bytes exceeds the {} byte limit",
+                size, MAX_PAYLOAD_SIZE
+            ));
+        }
+        if self.queue.len() >= MAX_QUEUED_MESSAGES {
+            return Err(format!(
+                "Bus queue is full ({} messages)",
+                MAX_QUEUED_MESSAGES
+            ));
+        }
+        self.queue.push_back(Message { channel, payload });
+        Ok(())
+    }
+
+    /// Subscribes `owner`, running in the store `store_id`, to `channel`, replacing its
+    /// previous handler for that channel.
+    pub fn subscribe(
+        &mut self,
+        channel: String,
+        owner: ExtensionIdentifier,
+        store_id: u64,
+        handler: BusHandler,
+    ) {
+        let subscriptions = self.subscriptions.entry(channel).or_default();
+        subscriptions.retain(|subscription| subscription.owner != owner);
+        subscriptions.push(Subscription {
+            owner,
+            store_id,
+            handler,
+        });
+    }
+
+    pub fn unsubscribe(&mut self, channel: &str, owner: &ExtensionIdentifier) -> bool {
+        let Some(subscriptions) = self.subscriptions.get_mut(channel) else {
+            return false;
+        };
+        let before = subscriptions.len();
+        subscriptions.retain(|subscription| &subscription.owner != owner);
+        let removed = subscriptions.len() != before;
+        if subscriptions.is_empty() {
+            self.subscriptions.remove(channel);
+        }
+        removed
+    }
+
+    /// Removes every subscription of `owner`.
+    pub fn unsubscribe_owned(&mut self, owner: &ExtensionIdentifier) {
+        self.subscriptions.retain(|_, subscriptions| {
+            subscriptions.retain(|subscription| &subscription.owner != owner);
+            !subscriptions.is_empty()
+        });
+    }
+
+    /// Empties the queue into one delivery per message and current subscriber, in publish
+    /// order. Messages on channels without subscribers are dropped.
+    pub fn drain(&mut self) -> Vec<Delivery> {
+        let mut deliveries = Vec::new();
+        for message in self.queue.drain(..) {
+            let Some(subscriptions) = self.subscriptions.get(&message.channel) else {
+                continue;
+            };
+            for subscription in subscriptions {
+                deliveries.push(Delivery {
+                    owner: subscription.owner.clone(),
+                    store_id: subscription.store_id,
+                    handler: subscription.handler,
+                    channel: message.channel.clone(),
+                    payload: message.payload.clone(),
+                });
+            }
+        }
+        deliveries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::testing;
+
+    /// Component exporting a bus handler that ignores its messages.
+    static HANDLER_COMPONENT: &str = r#"
+        (component
+            (core module $m
+                (memory (export "memory") 1)
+                (func (export "handle") (param i32 i32 i32 i32 i32))
+                (func (export "realloc") (param i32 i32 i32 i32) (result i32) i32.const 0))
+            (core instance $i (instantiate $m))
+            (type $payload' (variant (case "bytes" (list u8)) (case "json" string)))
+            (export $payload "payload" (type $payload'))
+            (func (export "handle") (param "channel" string) (param "payload" $payload)
+                (canon lift (core func $i "handle") (memory (core memory $i "memory"))
+                    (realloc (core func $i "realloc")))))
+    "#;
+
+    fn handler() -> BusHandler {
+        testing::component_func(HANDLER_COMPONENT, "handle").1
+    }
+
+    fn owner(name: &str) -> ExtensionIdentifier {
+        name.parse().unwrap()
+    }
+
+    fn json(text: &str) -> Payload {
+        Payload::Json(text.to_string())
+    }
+
+    fn summary(deliveries: &[Delivery]) -> Vec<(String, u64, String)> {
+        deliveries
+            .iter()
+            .map(|delivery| {
+                let Payload::Json(text) = &delivery.payload else {
+                    panic!("unexpected payload");
+                };
+                (delivery.owner.to_string(), delivery.store_id, text.clone())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delivers_to_current_subscribers_in_publish_order() {
+        let handler = handler();
+        let mut bus = Bus::default();
+        bus.subscribe("a".to_string(), owner("acme:one"), 1, handler);
+        bus.subscribe("a".to_string(), owner("acme:two"), 2, handler);
+        bus.subscribe("b".to_string(), owner("acme:one"), 1, handler);
+        bus.subscribe("a".to_string(), owner("acme:one"), 3, handler);
+        bus.publish("a".to_string(), json("1")).unwrap();
+        bus.publish("c".to_string(), json("2")).unwrap();
+        bus.publish("b".to_string(), json("3")).unwrap();
+        assert_eq!(
+            summary(&bus.drain()),
+            [
+                ("acme:two".to_string(), 2, "1".to_string()),
+                ("acme:one".to_string(), 3, "1".to_string()),
+                ("acme:one".to_string(), 1, "3".to_string()),
+            ]
+        );
+        assert!(bus.drain().is_empty());
+    }
+
+    #[test]
+    fn drops_messages_of_unsubscribed_owners() {
+        let handler = handler();
+        let mut bus = Bus::default();
+        bus.subscribe("a".to_string(), owner("acme:one"), 1, handler);
+        bus.subscribe("b".to_string(), owner("acme:one"), 1, handler);
+        bus.subscribe("b".to_string(), owner("acme:two"), 2, handler);
+        assert!(bus.unsubscribe("a", &owner("acme:one")));
+        assert!(!bus.unsubscribe("a", &owner("acme:one")));
+        bus.unsubscribe_owned(&owner("acme:two"));
+        bus.publish("a".to_string(), json("1")).unwrap();
+        bus.publish("b".to_string(), json("2")).unwrap();
+        assert_eq!(
+            summary(&bus.drain()),
+            [("acme:one".to_string(), 1, "2".to_string())]
+        );
+    }
+
+    #[test]
+    fn limits_payloads_and_queue() {
+        let mut bus = Bus::default();
+        let oversized = Payload::Bytes(vec![0; MAX_PAYLOAD_SIZE + 1]);
+        assert!(bus.publish("a".to_string(), oversized).is_err());
+        for _ in 0..MAX_QUEUED_MESSAGES {
+            bus.publish("a".to_string(), json("{}")).unwrap();
+        }
+        assert!(bus.publish("a".to_string(), json("{}")).is_err());
+        bus.drain();
+        bus.publish("a".to_string(), json("{}")).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    io::ErrorKind,
+    path::{self, Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use log::{debug, warn};
+use wasmtime::{Engine, Module, Precompiled, component::Component};
+
+use crate::extension::{config::CacheSettings, package::PackageHash, wasm::ExtensionBinary};
+
+static ARTIFACT_SUFFIX: &str = ".cwasm";
+
+/// Distinguishes the temporary files of concurrent writes within this process.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// On-disk store of compiled extension binaries. Artifacts are keyed by the hash of the
+/// package they were compiled from and by the compatibility hash of the engine they were
+/// compiled for, so changing either the package or the engine configuration misses the
+/// cache instead of loading incompatible code.
+pub struct ArtifactCache {
+    directory: PathBuf,
+    max_size: u64,
+}
+
+impl ArtifactCache {
+    pub fn new(settings: &CacheSettings) -> anyhow::Result<Self> {
+        let directory = path::absolute(&settings.directory)?;
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            max_size: settings.max_size,
+        })
+    }
+
+    pub fn load(&self, engine: &Engine, package: &PackageHash) -> Option<ExtensionBinary> {
+        let path = self.artifact_path(engine, package);
+        let bytes = std::fs::read(&path).ok()?;
+        // SAFETY: the cache directory only holds artifacts serialized by `store` for an
+        // engine with the same compatibility hash.
+        match unsafe { deserialize(engine, &bytes) } {
+            Ok(binary) => Some(binary),
+            Err(err) => {
+                warn!("Discarding cached artifact {}: {}", path.display(), err);
+                let _ = std::fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    pub fn store(
+        &self,
+        engine: &Engine,
+        package: &PackageHash,
+        binary: &ExtensionBinary,
+    ) -> anyhow::Result<()> {
+        let bytes = match binary {
+            ExtensionBinary::Component(component) => component.serialize()?,
+            ExtensionBinary::Module(module) => module.serialize()?,
+        };
+        let path = self.artifact_path(engine, package);
+        let temp = temp_path(&path);
+        let written = std::fs::write(&temp, bytes).and_then(|_| std::fs::rename(&temp, &path));
+        if let Err(err) = written {
+            let _ = std::fs::remove_file(&temp);
+            return Err(err.into());
+        }
+        self.trim()
+    }
+
+    fn artifact_path(&self, engine: &Engine, package: &PackageHash) -> PathBuf {
+        self.directory.join(format!(
+            "{}-{}{}",
+            package,
+            engine_key(engine),
+            ARTIFACT_SUFFIX
+        ))
+    }
+
+    /// Deletes the least recently written artifacts until the cache fits in `max_size`.
+    fn trim(&self) -> anyhow::Result<()> {
+        let mut artifacts: Vec<(PathBuf, u64, SystemTime)> = Vec::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            let entry = entry?;
+            let path = entry.path();
+            if !path.to_string_lossy().ends_with(ARTIFACT_SUFFIX) {
+                continue;
+            }
+            // Artifacts may be evicted by a concurrent trim while the directory is read.
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            artifacts.push((path, metadata.len(), modified));
+        }
+        let mut total: u64 = artifacts.iter().map(|(_, size, _)| size).sum();
+        artifacts.sort_by_key(|(_, _, modified)| *modified);
+        for (path, size, _) in artifacts {
+            if total <= self.max_size {
+                break;
+            }
+            debug!("Evicting cached artifact {}", path.display());
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+            total -= size;
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes a precompiled component or module.
+///
+/// # Safety
+/// `bytes` must come from `Component::serialize`, `Module::serialize` or the matching
+/// `Engine::precompile_*` function; wasmtime loads them as native code.
+pub unsafe fn deserialize(engine: &Engine, bytes: &[u8]) -> anyhow::Result<ExtensionBinary> {
+    match Engine::detect_precompiled(bytes) {
+        Some(Precompiled::Component) => Ok(ExtensionBinary::Component(unsafe {
+            Component::deserialize(engine, bytes)?
+        })),
+        Some(Precompiled::Module) => Ok(ExtensionBinary::Module(unsafe {
+            Module::deserialize(engine, bytes)?
+        })),
+        None => Err(anyhow::anyhow!("Not a precompiled wasmtime artifact")),
+    }
+}
+
+/// Temporary file `path` is written to before being renamed into place, unique to this
+/// process and write so that parallel loads of the same package don't clobber each other.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let counter = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.{}.tmp", std::process::id(), counter));
+    path.with_file_name(name)
+}
+
+fn engine_key(engine: &Engine) -> String {
+    let mut hasher = DefaultHasher::new();
+    engine.precompile_compatibility_hash().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, time::Duration};
+
+    use wasmtime::Config;
+
+    use super::*;
+
+    /// Cache in its own directory under the system temp directory.
+    fn cache(name: &str) -> ArtifactCache {
+        let settings = CacheSettings {
+            directory: std::env::temp_dir().join(format!(
+                "ark-cache-{}-{}",
+                std::process::id(),
+                name
+            )),
+            max_size: u64::MAX,
+            ..CacheSettings::default()
+        };
+        let _ = std::fs::remove_dir_all(&settings.directory);
+        ArtifactCache::new(&settings).unwrap()
+    }
+
+    fn component(engine: &Engine) -> ExtensionBinary {
+        ExtensionBinary::Component(Component::new(engine, "(component)").unwrap())
+    }
+
+    #[test]
+    fn loads_stored_artifacts() {
+        let engine = Engine::default();
+        let cache = cache("round-trip");
+        let package = PackageHash::of(b"package");
+        assert!(cache.load(&engine, &package).is_none());
+        cache.store(&engine, &package, &component(&engine)).unwrap();
+        assert!(matches!(
+            cache.load(&engine, &package),
+            Some(ExtensionBinary::Component(_))
+        ));
+        std::fs::remove_dir_all(&cache.directory).unwrap();
+    }
+
+    #[test]
+    fn misses_other_packages_and_engines() {
+        let engine = Engine::default();
+        let cache = cache("mismatch");
+        let package = PackageHash::of(b"package");
+        cache.store(&engine, &package, &component(&engine)).unwrap();
+        assert!(cache.load(&engine, &PackageHash::of(b"other")).is_none());
+
+        let fuel_engine = Engine::new(Config::new().consume_fuel(true)).unwrap();
+        assert_ne!(engine_key(&fuel_engine), engine_key(&engine));
+        assert!(cache.load(&fuel_engine, &package).is_none());
+        assert!(cache.load(&engine, &package).is_some());
+        std::fs::remove_dir_all(&cache.directory).unwrap();
+    }
+
+    #[test]
+    fn trims_oldest_artifacts_first() {
+        let engine = Engine::default();
+        let mut cache = cache("trim");
+        let packages = [b"a", b"b", b"c"].map(|name| PackageHash::of(name));
+        for package in &packages {
+            cache.store(&engine, package, &component(&engine)).unwrap();
+        }
+        let now = SystemTime::now();
+        for (package, age) in packages.iter().zip([30, 10, 20]) {
+            let file = File::options()
+                .write(true)
+                .open(cache.artifact_path(&engine, package))
+                .unwrap();
+            file.set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+        let size = std::fs::metadata(cache.artifact_path(&engine, &packages[0]))
+            .unwrap()
+            .len();
+        cache.max_size = size * 2;
+        cache.trim().unwrap();
+        let kept = packages.map(|package| cache.artifact_path(&engine, &package).exists());
+        assert_eq!(kept, [false, true, true]);
+
+        cache.max_size = size;
+        cache.trim().unwrap();
+        let kept = packages.map(|package| cache.artifact_path(&engine, &package).exists());
+        assert_eq!(kept, [false, true, false]);
+        std::fs::remove_dir_all(&cache.directory).unwrap();
+    }
+}
@@ -0,0 +1,471 @@
+use std::fmt::Display;
+
+use semver::Version;
+use wasmtime::{
+    Engine,
+    component::{
+        Component, InstancePre, Linker,
+        types::{self, ComponentItem, Type},
+    },
+};
+
+use crate::extension::{
+    binding::WorldVersion,
+    package::ExtensionManifest,
+    service::{self, ServiceImport},
+    wasm::{ExtensionContext, RESTORE_STATE_EXPORT, SAVE_STATE_EXPORT},
+};
+
+/// WASI preview2 release implemented by `wasmtime_wasi::p2`.
+static WASI_VERSION: &str = "0.2.6";
+
+static WASI_INTERFACES: &[&str] = &[
+    "wasi:cli/environment",
+    "wasi:cli/exit",
+    "wasi:cli/stdin",
+    "wasi:cli/stdout",
+    "wasi:cli/stderr",
+    "wasi:cli/terminal-input",
+    "wasi:cli/terminal-output",
+    "wasi:cli/terminal-stdin",
+    "wasi:cli/terminal-stdout",
+    "wasi:cli/terminal-stderr",
+    "wasi:clocks/monotonic-clock",
+    "wasi:clocks/wall-clock",
+    "wasi:filesystem/preopens",
+    "wasi:filesystem/types",
+    "wasi:io/error",
+    "wasi:io/poll",
+    "wasi:io/streams",
+    "wasi:random/insecure",
+    "wasi:random/insecure-seed",
+    "wasi:random/random",
+    "wasi:sockets/instance-network",
+    "wasi:sockets/ip-name-lookup",
+    "wasi:sockets/network",
+    "wasi:sockets/tcp",
+    "wasi:sockets/tcp-create-socket",
+    "wasi:sockets/udp",
+    "wasi:sockets/udp-create-socket",
+];
+
+#[derive(Debug)]
+pub enum CompatibilityIssue {
+    MissingImport {
+        name: String,
+        expected_version: Option<String>,
+    },
+    VersionMismatch {
+        name: String,
+        expected_version: String,
+        provided_version: String,
+    },
+    TypeMismatch {
+        name: String,
+        detail: String,
+    },
+    InvalidExport {
+        name: String,
+        detail: String,
+    },
+}
+
+/// Every reason a component can't be linked against the host, collected before
+/// instantiation instead of failing on the first unresolved import.
+#[derive(Debug)]
+pub struct CompatibilityReport {
+    pub issues: Vec<CompatibilityIssue>,
+}
+
+/// Picks the `ark:core` world version a component was built against from the versions in
+/// its import names. Components that import nothing from `ark:core` get the latest world.
+pub fn detect_world_version(
+    engine: &Engine,
+    component: &Component,
+) -> anyhow::Result<WorldVersion> {
+    let ty = component.component_type();
+    let mut detected: Option<(WorldVersion, String)> = None;
+    for (name, _) in ty.imports(engine) {
+        let world = if let Some(interface) = name.strip_prefix("ark:core/") {
+            let Some((_, version)) = interface.split_once('@') else {
+                continue;
+            };
+            match WorldVersion::from_version(version) {
+                Some(world) => world,
+                None => continue,
+            }
+        } else if WorldVersion::V0_1.functions().contains(&name) {
+            WorldVersion::V0_1
+        } else {
+            continue;
+        };
+        match &detected {
+            Some((previous, previous_name)) if *previous != world => {
+                return Err(anyhow::anyhow!(
+                    "Component mixes ark:core world versions: `{}` is from {} but `{}` is from {}",
+                    previous_name,
+                    previous.version(),
+                    name,
+                    world.version()
+                ));
+            }
+            Some(_) => {}
+            None => detected = Some((world, name.to_string())),
+        }
+    }
+    Ok(detected.map_or(WorldVersion::LATEST, |(world, _)| world))
+}
+
+/// Checks the component's imports against the `world` version of the host, WASI and the
+/// `services` resolved from its dependencies, and the exports named by the manifest or by
+/// the `stateful` world, then pre-instantiates it with `linker`.
+pub fn link_component(
+    engine: &Engine,
+    linker: &Linker<ExtensionContext>,
+    component: &Component,
+    manifest: &ExtensionManifest,
+    world: WorldVersion,
+    services: &[ServiceImport],
+) -> anyhow::Result<InstancePre<ExtensionContext>> {
+    let ty = component.component_type();
+    let mut issues = Vec::new();
+
+    let import_names: Vec<&str> = ty.imports(engine).map(|(name, _)| name).collect();
+    for name in &import_names {
+        if services.iter().any(|service| service.name == *name) {
+            continue;
+        }
+        if let Some(issue) = check_import(name, world) {
+            issues.push(issue);
+        }
+    }
+    issues.extend(service::check_provided(engine, &ty, manifest));
+
+    let exports = std::iter::once(&manifest.entry_function).chain(&manifest.close_function);
+    for name in exports {
+        issues.extend(check_export(
+            engine,
+            &ty,
+            name,
+            "a function without parameters or results",
+            |func| func.params().len() == 0 && func.results().len() == 0,
+        ));
+    }
+    if let Some(name) = &manifest.migrate_function {
+        issues.extend(check_export(
+            engine,
+            &ty,
+            name,
+            "a function taking one string and without results",
+            |func| {
+                let mut params = func.params();
+                matches!(params.next(), Some((_, Type::String)))
+                    && params.next().is_none()
+                    && func.results().len() == 0
+            },
+        ));
+    }
+    issues.extend(check_export(
+        engine,
+        &ty,
+        SAVE_STATE_EXPORT,
+        "a function without parameters returning list<u8>",
+        |func| {
+            let mut results = func.results();
+            func.params().len() == 0
+                && results.next().as_ref().is_some_and(is_byte_list)
+                && results.next().is_none()
+        },
+    ));
+    issues.extend(check_export(
+        engine,
+        &ty,
+        RESTORE_STATE_EXPORT,
+        "a function taking one list<u8> and without results",
+        |func| {
+            let mut params = func.params();
+            params.next().is_some_and(|(_, ty)| is_byte_list(&ty))
+                && params.next().is_none()
+                && func.results().len() == 0
+        },
+    ));
+
+    if !issues.is_empty() {
+        return Err(CompatibilityReport { issues }.into());
+    }
+    linker.instantiate_pre(component).map_err(|err| {
+        let detail = format!("{:#}", err);
+        let name = import_names
+            .iter()
+            .find(|name| detail.contains(&format!("`{}`", name)))
+            .map_or("<unknown>".to_string(), |name| name.to_string());
+        CompatibilityReport {
+            issues: vec![CompatibilityIssue::TypeMismatch { name, detail }],
+        }
+        .into()
+    })
+}
+
+/// Checks an optional function export; exports the component doesn't have are fine.
+fn check_export(
+    engine: &Engine,
+    ty: &types::Component,
+    name: &str,
+    expected: &str,
+    is_valid: impl Fn(&types::ComponentFunc) -> bool,
+) -> Option<CompatibilityIssue> {
+    let detail = match ty.get_export(engine, name)? {
+        ComponentItem::ComponentFunc(func) if is_valid(&func) => return None,
+        ComponentItem::ComponentFunc(_) => format!("expected {}", expected),
+        _ => "expected a function".to_string(),
+    };
+    Some(CompatibilityIssue::InvalidExport {
+        name: name.to_string(),
+        detail,
+    })
+}
+
+fn is_byte_list(ty: &Type) -> bool {
+    matches!(ty, Type::List(list) if matches!(list.ty(), Type::U8))
+}
+
+fn check_import(name: &str, world: WorldVersion) -> Option<CompatibilityIssue> {
+    let (base, version) = match name.split_once('@') {
+        Some((base, version)) => (base, Some(version)),
+        None => (name, None),
+    };
+    let provided = if WASI_INTERFACES.contains(&base) {
+        WASI_VERSION
+    } else if world.interfaces().contains(&base) {
+        world.version()
+    } else if version.is_none() && world.functions().contains(&base) {
+        return None;
+    } else {
+        return Some(CompatibilityIssue::MissingImport {
+            name: base.to_string(),
+            expected_version: version.map(str::to_string),
+        });
+    };
+    let expected = version?;
+    if is_semver_compatible(expected, provided) {
+        None
+    } else {
+        Some(CompatibilityIssue::VersionMismatch {
+            name: base.to_string(),
+            expected_version: expected.to_string(),
+            provided_version: provided.to_string(),
+        })
+    }
+}
+
+/// Mirrors the linker's lookup rule: versions match on the major version, or on the minor
+/// version while the major version is 0.
+pub(crate) fn is_semver_compatible(expected: &str, provided: &str) -> bool {
+    match (Version::parse(expected), Version::parse(provided)) {
+        (Ok(expected), Ok(provided)) => {
+            if expected.major != provided.major {
+                false
+            } else if expected.major == 0 {
+                expected.minor == provided.minor
+            } else {
+                true
+            }
+        }
+        _ => expected == provided,
+    }
+}
+
+impl Display for CompatibilityIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompatibilityIssue::MissingImport {
+                name,
+                expected_version: Some(version),
+            } => write!(
+                f,
+                "import `{}@{}` is not provided by the host",
+                name, version
+            ),
+            CompatibilityIssue::MissingImport {
+                name,
+                expected_version: None,
+            } => write!(f, "import `{}` is not provided by the host", name),
+            CompatibilityIssue::VersionMismatch {
+                name,
+                expected_version,
+                provided_version,
+            } => write!(
+                f,
+                "import `{}` expects version {}, but the host provides {}",
+                name, expected_version, provided_version
+            ),
+            CompatibilityIssue::TypeMismatch { name, detail } => {
+                write!(f, "import `{}` has a mismatched type: {}", name, detail)
+            }
+            CompatibilityIssue::InvalidExport { name, detail } => {
+                write!(f, "export `{}` is invalid: {}", name, detail)
+            }
+        }
+    }
+}
+
+impl Display for CompatibilityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Extension component is incompatible with the host:")?;
+        for issue in &self.issues {
+            write!(f, "\n  - {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompatibilityReport {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(entry_function: &str, close_function: Option<&str>) -> ExtensionManifest {
+        serde_json::from_value(serde_json::json!({
+            "id": "tools",
+            "entry_function": entry_function,
+            "close_function": close_function,
+        }))
+        .unwrap()
+    }
+
+    fn issues(
+        wat: &str,
+        manifest: &ExtensionManifest,
+        services: &[ServiceImport],
+    ) -> Vec<CompatibilityIssue> {
+        let engine = Engine::default();
+        let component = Component::new(&engine, wat).unwrap();
+        let world = WorldVersion::LATEST;
+        let linker = Linker::new(&engine);
+        match link_component(&engine, &linker, &component, manifest, world, services) {
+            Ok(_) => Vec::new(),
+            Err(err) => err.downcast::<CompatibilityReport>().unwrap().issues,
+        }
+    }
+
+    #[test]
+    fn zero_major_versions_match_on_minor() {
+        assert!(is_semver_compatible("0.1.0", "0.1.7"));
+        assert!(!is_semver_compatible("0.1.0", "0.2.0"));
+        assert!(!is_semver_compatible("0.2.0", "0.1.0"));
+        assert!(is_semver_compatible("1.2.0", "1.0.3"));
+        assert!(!is_semver_compatible("1.0.0", "2.0.0"));
+        assert!(is_semver_compatible("latest", "latest"));
+        assert!(!is_semver_compatible("latest", "0.1.0"));
+    }
+
+    #[test]
+    fn checks_imports_against_the_host() {
+        let world = WorldVersion::V0_1;
+        assert!(check_import("ark:core/logging@0.1.2", world).is_none());
+        assert!(check_import("wasi:io/streams@0.2.0", world).is_none());
+        assert!(check_import("register", world).is_none());
+        assert!(matches!(
+            check_import("ark:core/logging@0.2.0", world),
+            Some(CompatibilityIssue::VersionMismatch { expected_version, provided_version, .. })
+                if expected_version == "0.2.0" && provided_version == "0.1.0"
+        ));
+        assert!(matches!(
+            check_import("acme:tools/api@1.0.0", world),
+            Some(CompatibilityIssue::MissingImport { name, expected_version: Some(version) })
+                if name == "acme:tools/api" && version == "1.0.0"
+        ));
+    }
+
+    #[test]
+    fn checks_imports_against_the_detected_world() {
+        assert!(check_import("ark:core/package@0.2.0", WorldVersion::V0_2).is_none());
+        assert!(check_import("ark:core/package@0.1.0", WorldVersion::V0_1).is_some());
+        assert!(check_import("register", WorldVersion::V0_1).is_none());
+        assert!(check_import("register", WorldVersion::V0_2).is_some());
+    }
+
+    fn world_version(wat: &str) -> anyhow::Result<WorldVersion> {
+        let engine = Engine::default();
+        detect_world_version(&engine, &Component::new(&engine, wat).unwrap())
+    }
+
+    #[test]
+    fn detects_world_versions() {
+        let v0_1 = r#"(component
+            (import "ark:core/logging@0.1.0" (instance))
+            (import "register" (func (param "trigger" string) (param "function" string))))"#;
+        let v0_2 = r#"(component (import "ark:core/host@0.2.0" (instance)))"#;
+        assert_eq!(world_version(v0_1).unwrap(), WorldVersion::V0_1);
+        assert_eq!(world_version(v0_2).unwrap(), WorldVersion::V0_2);
+        assert_eq!(world_version("(component)").unwrap(), WorldVersion::LATEST);
+        let mixed = r#"(component
+            (import "ark:core/logging@0.1.0" (instance))
+            (import "ark:core/host@0.2.0" (instance)))"#;
+        assert!(world_version(mixed).is_err());
+    }
+
+    #[test]
+    fn reports_every_missing_import() {
+        let wat = r#"(component
+            (import "acme:tools/api@1.0.0" (instance))
+            (import "acme:other/api" (instance)))"#;
+        let issues = issues(wat, &manifest("init", None), &[]);
+        assert_eq!(issues.len(), 2);
+        assert!(
+            issues
+                .iter()
+                .all(|issue| matches!(issue, CompatibilityIssue::MissingImport { .. }))
+        );
+    }
+
+    #[test]
+    fn reports_invalid_exports() {
+        let wat = r#"(component
+            (core module $m (func (export "init") (param i32)))
+            (core instance $i (instantiate $m))
+            (func (export "init") (param "value" u32) (canon lift (core func $i "init")))
+            (export "close" (core module $m)))"#;
+        let issues = issues(wat, &manifest("init", Some("close")), &[]);
+        let names: Vec<&str> = issues
+            .iter()
+            .map(|issue| match issue {
+                CompatibilityIssue::InvalidExport { name, .. } => name.as_str(),
+                issue => panic!("unexpected issue: {}", issue),
+            })
+            .collect();
+        assert_eq!(names, ["init", "close"]);
+    }
+
+    #[test]
+    fn accepts_missing_optional_exports() {
+        let wat = r#"(component
+            (core module $m (func (export "init")))
+            (core instance $i (instantiate $m))
+            (func (export "init") (canon lift (core func $i "init"))))"#;
+        assert!(issues(wat, &manifest("init", Some("close")), &[]).is_empty());
+    }
+
+    #[test]
+    fn skips_imports_served_by_dependencies() {
+        let wat = r#"(component (import "acme:tools/api@1.0.0" (instance)))"#;
+        let services = [ServiceImport {
+            name: "acme:tools/api@1.0.0".to_string(),
+            provider: "acme:tools".parse().unwrap(),
+            export: "acme:tools/api@1.1.0".to_string(),
+        }];
+        assert!(issues(wat, &manifest("init", None), &services).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_provided_services() {
+        let mut manifest = manifest("init", None);
+        manifest.provides = vec!["acme:tools/api@1.0.0".to_string()];
+        let issues = issues("(component)", &manifest, &[]);
+        assert!(matches!(
+            &issues[..],
+            [CompatibilityIssue::InvalidExport { name, .. }] if name == "acme:tools/api@1.0.0"
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+pub static HOST_CONFIG_FILE: &str = "host.json";
+
+/// Host-side runtime settings, read from the extension folder next to the policy file.
+#[derive(Debug, Default, Deserialize)]
+pub struct HostConfig {
+    #[serde(default)]
+    pub cache: CacheSettings,
+    #[serde(default)]
+    pub pooling: PoolingSettings,
+    #[serde(default)]
+    pub jobs: JobSettings,
+    #[serde(default)]
+    pub profiling: ProfilingSettings,
+    #[serde(default)]
+    pub metrics: MetricsSettings,
+    #[serde(default)]
+    pub debug: DebugSettings,
+    #[serde(default)]
+    pub crash_reports: CrashReportSettings,
+    #[serde(default)]
+    pub safe_mode: SafeModeSettings,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    /// Directory compiled artifacts are stored in, relative to the working directory.
+    pub directory: PathBuf,
+    /// Total size in bytes the cache is trimmed to, oldest artifacts first.
+    pub max_size: u64,
+    /// Whether `.cwasm` artifacts shipped inside packages may be loaded. They are native
+    /// code, so only packages from trusted sources should be allowed to provide them.
+    pub trust_precompiled: bool,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: PathBuf::from("./cache/ark/"),
+            max_size: 512 * 1024 * 1024,
+            trust_precompiled: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct PoolingSettings {
+    /// Whether instances are allocated from a preallocated pool instead of on demand.
+    pub enabled: bool,
+    /// Memory slot size, in bytes, for extensions that don't declare `max_memory`.
+    pub default_memory: u64,
+    /// Upper bound on the memory slot size, whatever extensions declare.
+    pub max_memory: u64,
+    /// Slots kept on top of two per installed extension, for packages added while running.
+    pub spare_instances: u32,
+}
+
+impl Default for PoolingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_memory: 64 * 1024 * 1024,
+            max_memory: 1024 * 1024 * 1024,
+            spare_instances: 8,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct JobSettings {
+    /// Worker threads running background jobs; 0 picks one less than the number of cores.
+    pub threads: usize,
+    /// Milliseconds a background job may run before it is abandoned and fails; 0 lets jobs
+    /// run to completion. Timing jobs out relies on epoch interruption.
+    pub timeout_ms: u64,
+}
+
+impl JobSettings {
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_ms > 0).then(|| Duration::from_millis(self.timeout_ms))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ProfilingSettings {
+    /// Whether extensions can be profiled. Sampling relies on epoch interruption, which
+    /// makes all guest code slightly slower, so it is off unless enabled here.
+    pub enabled: bool,
+    /// Milliseconds between samples.
+    pub interval_ms: u64,
+    /// Directory profiles are written to, relative to the working directory.
+    pub directory: PathBuf,
+}
+
+impl Default for ProfilingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 1,
+            directory: PathBuf::from("./profiles/ark/"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MetricsSettings {
+    /// Whether guests are metered with fuel so that metrics report the fuel each trigger
+    /// consumes. Metering adds a check to every guest loop and call, so it is off by default.
+    pub fuel: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct DebugSettings {
+    /// Whether every extension runs in debug mode.
+    pub all: bool,
+    /// `namespace:id` of the extensions to run in debug mode, on top of those whose manifest
+    /// sets `runtime.debug`.
+    pub extensions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CrashReportSettings {
+    /// Whether a report file is written whenever a call into an extension fails.
+    pub enabled: bool,
+    /// Directory reports are written to, relative to the working directory.
+    pub directory: PathBuf,
+    /// Reports kept in the directory, oldest deleted first.
+    pub max_reports: usize,
+    /// Minimum time between two reports for the same extension and trigger. Failures in
+    /// between are only counted, in the next report.
+    pub min_interval_ms: u64,
+}
+
+impl Default for CrashReportSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: PathBuf::from("./crash-reports/ark/"),
+            max_reports: 100,
+            min_interval_ms: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SafeModeSettings {
+    /// Whether extensions that crash the process while initializing are skipped on the next
+    /// start.
+    pub enabled: bool,
+    /// Whether every extension is skipped after such a crash, not just the ones that were
+    /// initializing.
+    pub skip_all: bool,
+}
+
+impl Default for SafeModeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            skip_all: false,
+        }
+    }
+}
+
+impl HostConfig {
+    /// Reads the host config from `folder`, or returns the defaults if there is none.
+    pub fn load(folder: &Path) -> anyhow::Result<Self> {
+        let path = folder.join(HOST_CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+}
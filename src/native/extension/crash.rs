@@ -0,0 +1,370 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{self, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use wasmtime::{Trap, WasmBacktrace};
+
+use crate::extension::{
+    cache,
+    config::HostConfig,
+    package::ExtensionIdentifier,
+    wasm::{ExtensionBinary, ExtensionContext},
+};
+
+/// Log lines of an extension kept for its crash reports.
+pub static MAX_RECENT_LOGS: usize = 64;
+
+static REPORT_SUFFIX: &str = ".json";
+/// Sequence number of the next report written by this process.
+static NEXT_REPORT: AtomicU64 = AtomicU64::new(0);
+
+/// The last [`MAX_RECENT_LOGS`] lines an extension logged, whatever the host's log level.
+#[derive(Debug, Default)]
+pub struct RecentLogs(VecDeque<String>);
+
+impl RecentLogs {
+    pub fn push(&mut self, level: &str, message: &str) {
+        if self.0.len() >= MAX_RECENT_LOGS {
+            self.0.pop_front();
+        }
+        self.0.push_back(format!("{} {}", level, message));
+    }
+}
+
+/// What every report records about the engines, fixed for the lifetime of the runtime.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineSettings {
+    pub pooling: bool,
+    pub profiling: bool,
+    pub fuel_metering: bool,
+}
+
+/// Capabilities the extension was granted when it was launched.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub wasi_features: Vec<String>,
+    pub vulkan_extensions: Vec<String>,
+    pub vulkan_features: Vec<String>,
+    pub bus_publish: Vec<String>,
+    pub bus_subscribe: Vec<String>,
+    /// Imports served by other extensions.
+    pub services: Vec<String>,
+    pub max_memory: Option<u64>,
+}
+
+/// Engine the extension ran in.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EngineInfo {
+    #[serde(flatten)]
+    pub settings: EngineSettings,
+    pub debug: bool,
+    pub binary: String,
+    pub world_version: String,
+}
+
+/// One failed guest call, as written to its report file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub identifier: String,
+    pub package_hash: String,
+    pub trigger: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub error: String,
+    /// Trap code when the guest trapped, as opposed to a host function failing.
+    pub trap: Option<String>,
+    /// Guest frames, with file and line when the binary carries DWARF and the extension runs
+    /// in debug mode.
+    pub backtrace: Option<String>,
+    pub recent_logs: Vec<String>,
+    /// Failures of the same trigger that were not reported since the previous report.
+    #[serde(default)]
+    pub suppressed: u64,
+    pub capabilities: Capabilities,
+    pub engine: EngineInfo,
+}
+
+/// Entry of [`CrashReporter::list`].
+#[derive(Debug, Serialize)]
+pub struct CrashSummary {
+    pub path: String,
+    pub identifier: String,
+    pub trigger: String,
+    pub timestamp: u64,
+    pub error: String,
+}
+
+/// Writes a report file for failed guest calls into the configured directory, keeping at
+/// most `max_reports` of them. A guest failing on every tick gets one report per
+/// `min_interval_ms` rather than one per tick.
+pub struct CrashReporter {
+    directory: PathBuf,
+    max_reports: usize,
+    min_interval: Duration,
+    engine: EngineSettings,
+    /// Last report time and failures suppressed since, by extension and trigger.
+    windows: Mutex<HashMap<(String, String), (Instant, u64)>>,
+}
+
+impl CrashReporter {
+    pub fn new(config: &HostConfig) -> anyhow::Result<Self> {
+        let directory = path::absolute(&config.crash_reports.directory)?;
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            max_reports: config.crash_reports.max_reports,
+            min_interval: Duration::from_millis(config.crash_reports.min_interval_ms),
+            engine: EngineSettings {
+                pooling: config.pooling.enabled,
+                profiling: config.profiling.enabled,
+                fuel_metering: config.metrics.fuel,
+            },
+            windows: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Writes the report of `err`, returned by a call into the extension of `context` for
+    /// `trigger`, and returns the report's path, or `None` if the extension's last report for
+    /// `trigger` is too recent.
+    pub fn report(
+        &self,
+        context: &ExtensionContext,
+        trigger: &str,
+        err: &anyhow::Error,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let Some(suppressed) = self.admit(&context.identifier.to_string(), trigger) else {
+            return Ok(None);
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let manifest = &context.package.manifest;
+        let report = CrashReport {
+            identifier: context.identifier.to_string(),
+            package_hash: context.package.hash.to_string(),
+            trigger: trigger.to_string(),
+            timestamp,
+            error: format!("{:#}", err),
+            trap: trap_code(err),
+            backtrace: guest_backtrace(err),
+            recent_logs: context.recent_logs.0.iter().cloned().collect(),
+            suppressed,
+            capabilities: Capabilities {
+                wasi_features: context.launch_args.enabled_wasi_features.clone(),
+                vulkan_extensions: context.launch_args.enabled_vulkan_extensions.clone(),
+                vulkan_features: context.launch_args.enabled_vulkan_features.clone(),
+                bus_publish: manifest.bus.publish.clone(),
+                bus_subscribe: manifest.bus.subscribe.clone(),
+                services: context
+                    .services
+                    .iter()
+                    .map(|service| service.name.clone())
+                    .collect(),
+                max_memory: manifest.runtime.max_memory,
+            },
+            engine: EngineInfo {
+                settings: self.engine.clone(),
+                debug: context.debug,
+                binary: match context.binary {
+                    ExtensionBinary::Component(_) => "component",
+                    ExtensionBinary::Module(_) => "module",
+                }
+                .to_string(),
+                world_version: context.world_version.version().to_string(),
+            },
+        };
+        let path = self
+            .directory
+            .join(report_file_name(&context.identifier, timestamp));
+        let temp = cache::temp_path(&path);
+        let written = std::fs::write(&temp, serde_json::to_vec_pretty(&report)?)
+            .and_then(|()| std::fs::rename(&temp, &path));
+        if let Err(err) = written {
+            let _ = std::fs::remove_file(&temp);
+            return Err(err.into());
+        }
+        self.trim()?;
+        Ok(Some(path))
+    }
+
+    /// Starts a new report window for `identifier` and `trigger` if the last one is at least
+    /// `min_interval` old, returning how many failures the last one suppressed. Otherwise
+    /// counts the failure and returns `None`.
+    fn admit(&self, identifier: &str, trigger: &str) -> Option<u64> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        let key = (identifier.to_string(), trigger.to_string());
+        match windows.get_mut(&key) {
+            Some((start, suppressed)) if now.duration_since(*start) < self.min_interval => {
+                *suppressed += 1;
+                None
+            }
+            Some((start, suppressed)) => {
+                *start = now;
+                Some(std::mem::take(suppressed))
+            }
+            None => {
+                windows.insert(key, (now, 0));
+                Some(0)
+            }
+        }
+    }
+
+    /// Summarizes the reports in the directory, newest first, including those written by
+    /// earlier runs.
+    pub fn list(&self) -> anyhow::Result<Vec<CrashSummary>> {
+        let mut summaries = Vec::new();
+        for path in self.report_paths()? {
+            let Some(report) = std::fs::read(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<CrashReport>(&bytes).ok())
+            else {
+                continue;
+            };
+            summaries.push(CrashSummary {
+                path: path.to_string_lossy().into_owned(),
+                identifier: report.identifier,
+                trigger: report.trigger,
+                timestamp: report.timestamp,
+                error: report.error,
+            });
+        }
+        summaries.sort_by_key(|summary| std::cmp::Reverse(summary.timestamp));
+        Ok(summaries)
+    }
+
+    /// Deletes the oldest reports beyond `max_reports`.
+    fn trim(&self) -> anyhow::Result<()> {
+        let mut reports: Vec<(SystemTime, PathBuf)> = self
+            .report_paths()?
+            .into_iter()
+            .map(|path| {
+                let modified = std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (modified, path)
+            })
+            .collect();
+        if reports.len() <= self.max_reports {
+            return Ok(());
+        }
+        reports.sort();
+        let excess = reports.len() - self.max_reports;
+        for (_, path) in reports.into_iter().take(excess) {
+            let _ = std::fs::remove_file(path);
+        }
+        Ok(())
+    }
+
+    fn report_paths(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.to_string_lossy().ends_with(REPORT_SUFFIX) {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+}
+
+/// Trap code of `err` when the guest trapped, as opposed to a host function failing.
+fn trap_code(err: &anyhow::Error) -> Option<String> {
+    err.downcast_ref::<Trap>().map(ToString::to_string)
+}
+
+/// Guest frames captured when `err` was raised, if wasmtime captured any.
+fn guest_backtrace(err: &anyhow::Error) -> Option<String> {
+    err.downcast_ref::<WasmBacktrace>().map(ToString::to_string)
+}
+
+/// Name of a report file, made of characters every file system accepts since identifiers
+/// contain a `:`. The sequence number keeps reports written in the same millisecond apart.
+fn report_file_name(identifier: &ExtensionIdentifier, timestamp: u64) -> String {
+    let sequence = NEXT_REPORT.fetch_add(1, Ordering::Relaxed);
+    format!(
+        "{}-{}-{}{}",
+        identifier.file_stem(),
+        timestamp,
+        sequence,
+        REPORT_SUFFIX
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::testing::unreachable_error;
+
+    fn reporter(min_interval: Duration) -> CrashReporter {
+        CrashReporter {
+            directory: PathBuf::new(),
+            max_reports: 1,
+            min_interval,
+            engine: EngineSettings {
+                pooling: false,
+                profiling: false,
+                fuel_metering: false,
+            },
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[test]
+    fn records_guest_trap() {
+        let err = unreachable_error().context("Failed to run extension");
+        assert_eq!(
+            trap_code(&err),
+            Some(Trap::UnreachableCodeReached.to_string())
+        );
+        assert!(guest_backtrace(&err).is_some());
+        assert_eq!(trap_code(&anyhow::anyhow!("host function failed")), None);
+    }
+
+    #[test]
+    fn suppresses_repeated_reports() {
+        let reporter = reporter(Duration::from_secs(3600));
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "tick"), Some(0));
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "tick"), None);
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "tick"), None);
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "initialize"), Some(0));
+        assert_eq!(reporter.admit("acme:other@1.0.0", "tick"), Some(0));
+    }
+
+    #[test]
+    fn counts_suppressed_failures_in_next_report() {
+        let reporter = reporter(Duration::from_secs(1));
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "tick"), Some(0));
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "tick"), None);
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "tick"), None);
+        for (start, _) in reporter.windows.lock().unwrap().values_mut() {
+            *start -= Duration::from_secs(2);
+        }
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "tick"), Some(2));
+        assert_eq!(reporter.admit("acme:tools@1.0.0", "tick"), None);
+    }
+
+    #[test]
+    fn report_file_name_is_portable() {
+        let identifier: ExtensionIdentifier = "acme:tools@1.0.0".parse().unwrap();
+        let name = report_file_name(&identifier, 42);
+        assert!(name.starts_with("acme-tools-42-"), "{}", name);
+        assert!(name.ends_with(REPORT_SUFFIX), "{}", name);
+    }
+
+    #[test]
+    fn report_file_names_differ_within_a_millisecond() {
+        let identifier: ExtensionIdentifier = "acme:tools@1.0.0".parse().unwrap();
+        assert_ne!(
+            report_file_name(&identifier, 42),
+            report_file_name(&identifier, 42)
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use wasmtime::{Config, OptLevel, WasmBacktraceDetails};
+
+use crate::extension::{
+    config::HostConfig,
+    package::{ExtensionIdentifier, ExtensionManifest},
+};
+
+/// Whether `manifest`'s extension runs in debug mode, either because its manifest asks for
+/// it or because the host config lists it.
+pub fn is_enabled(config: &HostConfig, manifest: &ExtensionManifest) -> bool {
+    if config.debug.all || manifest.runtime.debug {
+        return true;
+    }
+    let key = ExtensionIdentifier::from_manifest(manifest)
+        .unversioned()
+        .to_string();
+    config
+        .debug
+        .extensions
+        .iter()
+        .any(|entry| entry.split('@').next() == Some(key.as_str()))
+}
+
+/// Turns `config` into the configuration of the debug engine: native debug info registered
+/// with the debugger's JIT interface, and no optimizations so that locals and line tables
+/// match the source. The guest's DWARF is kept so that the backtraces wasmtime attaches to
+/// traps name the file and line of each frame, which then show up in the error reports.
+pub fn configure_engine(config: &mut Config) {
+    config.debug_info(true);
+    config.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+    config.cranelift_opt_level(OptLevel::None);
+}
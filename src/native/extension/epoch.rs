@@ -0,0 +1,104 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use wasmtime::{Engine, Store, UpdateDeadline};
+
+use crate::extension::{profiling, wasm::ExtensionContext};
+
+/// Epoch deadline of stores that aren't running a background job, far enough out that they
+/// never reach it.
+static IDLE_DEADLINE: u64 = 1 << 40;
+
+/// Background thread that increments the epoch of every added engine once per interval,
+/// which is what makes running guests stop at their epoch deadline.
+pub struct EpochTicker {
+    engines: Arc<Mutex<Vec<Engine>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EpochTicker {
+    pub fn start(interval: Duration) -> anyhow::Result<Self> {
+        let engines: Arc<Mutex<Vec<Engine>>> = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::Builder::new()
+            .name("ark-epoch-ticker".to_string())
+            .spawn({
+                let engines = engines.clone();
+                let stop = stop.clone();
+                move || {
+                    while !stop.load(Ordering::Relaxed) {
+                        std::thread::sleep(interval);
+                        for engine in engines.lock().unwrap().iter() {
+                            engine.increment_epoch();
+                        }
+                    }
+                }
+            })?;
+        Ok(Self {
+            engines,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn add(&self, engine: &Engine) {
+        self.engines.lock().unwrap().push(engine.clone());
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Installs the epoch callback that samples the store's guest profile while one is recording
+/// and makes background jobs yield on every tick so that they can time out. Stores of an
+/// engine with epoch interruption need a deadline, so idle stores get one they never reach.
+pub fn configure_store(store: &mut Store<ExtensionContext>) {
+    store.set_epoch_deadline(IDLE_DEADLINE);
+    store.epoch_deadline_callback(|mut context| {
+        profiling::sample(&mut context);
+        Ok(if context.data().background {
+            UpdateDeadline::Yield(1)
+        } else if context.data().profile.is_some() {
+            UpdateDeadline::Continue(1)
+        } else {
+            UpdateDeadline::Continue(IDLE_DEADLINE)
+        })
+    });
+}
+
+/// Marks `store` as running a background job, which yields on the next epoch tick.
+pub fn start_background(store: &mut Store<ExtensionContext>) {
+    store.data_mut().background = true;
+    store.set_epoch_deadline(1);
+}
+
+/// Ends [`start_background`].
+pub fn finish_background(store: &mut Store<ExtensionContext>) {
+    store.data_mut().background = false;
+    reset_deadline(store);
+}
+
+/// Sets the deadline of `store` back to the next tick if it is still running a background job
+/// or recording a profile, or out of reach otherwise.
+pub fn reset_deadline(store: &mut Store<ExtensionContext>) {
+    let data = store.data();
+    let deadline = if data.background || data.profile.is_some() {
+        1
+    } else {
+        IDLE_DEADLINE
+    };
+    store.set_epoch_deadline(deadline);
+}
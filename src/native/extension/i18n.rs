@@ -0,0 +1,118 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+static LANG_DIR: &str = "lang/";
+pub static DEFAULT_LOCALE: &str = "en_us";
+pub static NAME_KEY: &str = "extension.name";
+pub static DESCRIPTION_KEY: &str = "extension.description";
+
+/// Strings loaded from the `lang/<locale>.json` files of a package, keyed by normalized
+/// locale and then by translation key.
+#[derive(Debug, Default)]
+pub struct Translations {
+    locales: HashMap<String, HashMap<String, String>>,
+}
+
+impl Translations {
+    pub fn from_files(files: &HashMap<String, Cow<'static, [u8]>>) -> anyhow::Result<Self> {
+        let mut locales = HashMap::new();
+        for (name, data) in files {
+            let Some(locale) = name
+                .strip_prefix(LANG_DIR)
+                .and_then(|file| file.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            if locale.contains('/') {
+                continue;
+            }
+            let strings: HashMap<String, String> = serde_json::from_slice(data)
+                .map_err(|e| anyhow::anyhow!("Failed to parse language file {}: {}", name, e))?;
+            locales.insert(normalize_locale(locale), strings);
+        }
+        Ok(Self { locales })
+    }
+
+    /// Looks `key` up along the fallback chain of `locale`: the exact locale, its bare
+    /// language, any other region of the same language, then the default locale.
+    pub fn translate(&self, locale: &str, key: &str) -> Option<&str> {
+        self.fallback_chain(locale)
+            .into_iter()
+            .find_map(|locale| self.locales.get(&locale)?.get(key))
+            .map(String::as_str)
+    }
+
+    fn fallback_chain(&self, locale: &str) -> Vec<String> {
+        let locale = normalize_locale(locale);
+        let language = locale.split('_').next().unwrap_or_default().to_string();
+        let mut regions: Vec<&String> = self
+            .locales
+            .keys()
+            .filter(|other| other.starts_with(&format!("{}_", language)))
+            .collect();
+        regions.sort();
+
+        let mut chain = vec![locale.clone(), language];
+        chain.extend(regions.into_iter().cloned());
+        chain.push(DEFAULT_LOCALE.to_string());
+        let mut seen = HashSet::new();
+        chain.retain(|locale| seen.insert(locale.clone()));
+        chain
+    }
+}
+
+/// Normalizes `en-US` and `en_US` style codes to Minecraft's `en_us`.
+pub fn normalize_locale(locale: &str) -> String {
+    locale.replace('-', "_").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translations(files: &[(&str, &str)]) -> Translations {
+        let files = files
+            .iter()
+            .map(|(name, json)| (name.to_string(), Cow::Owned(json.as_bytes().to_vec())))
+            .collect();
+        Translations::from_files(&files).unwrap()
+    }
+
+    #[test]
+    fn falls_back_along_the_locale_chain() {
+        let translations = translations(&[
+            (
+                "lang/en_us.json",
+                r#"{ "a": "en_us", "b": "en_us", "c": "en_us" }"#,
+            ),
+            ("lang/de.json", r#"{ "a": "de", "b": "de" }"#),
+            ("lang/de-AT.json", r#"{ "a": "de_at" }"#),
+            ("lang/de_ch.json", r#"{ "a": "de_ch", "d": "de_ch" }"#),
+        ]);
+        assert_eq!(translations.translate("de_AT", "a"), Some("de_at"));
+        assert_eq!(translations.translate("de-at", "b"), Some("de"));
+        assert_eq!(translations.translate("de_de", "a"), Some("de"));
+        assert_eq!(translations.translate("de_de", "d"), Some("de_ch"));
+        assert_eq!(translations.translate("de_de", "c"), Some("en_us"));
+        assert_eq!(translations.translate("fr_fr", "a"), Some("en_us"));
+        assert_eq!(translations.translate("fr_fr", "missing"), None);
+    }
+
+    #[test]
+    fn ignores_other_files() {
+        let translations = translations(&[
+            ("lang/nested/en_us.json", "not json"),
+            ("lang/en_us.txt", "not json"),
+            ("en_us.json", "not json"),
+        ]);
+        assert_eq!(translations.translate(DEFAULT_LOCALE, "a"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_language_files() {
+        let files = HashMap::from([("lang/en_us.json".to_string(), Cow::Borrowed(&b"["[..]))]);
+        assert!(Translations::from_files(&files).is_err());
+    }
+}
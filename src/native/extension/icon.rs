@@ -0,0 +1,170 @@
+use std::{io::Cursor, num::NonZeroU64};
+
+use png::{BitDepth, ColorType, Decoder, Limits, Transformations};
+
+/// Largest icon side accepted from a package; bigger images are rejected before decoding.
+pub static MAX_ICON_DIMENSION: u32 = 1024;
+
+#[derive(Debug, Clone)]
+pub struct Icon {
+    pub width: u32,
+    pub height: u32,
+    /// 8-bit RGBA pixels, row by row.
+    pub rgba: Vec<u8>,
+}
+
+impl Icon {
+    pub fn decode_png(data: &[u8]) -> anyhow::Result<Self> {
+        let limits = Limits {
+            bytes: (MAX_ICON_DIMENSION * MAX_ICON_DIMENSION * 16) as usize,
+        };
+        let mut decoder = Decoder::new_with_limits(Cursor::new(data), limits);
+        decoder.set_transformations(Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let (width, height) = (reader.info().width, reader.info().height);
+        if width == 0 || height == 0 || width > MAX_ICON_DIMENSION || height > MAX_ICON_DIMENSION {
+            return Err(anyhow::anyhow!(
+                "Icon dimensions {}x{} are outside 1x1..{}x{}",
+                width,
+                height,
+                MAX_ICON_DIMENSION,
+                MAX_ICON_DIMENSION
+            ));
+        }
+        let mut buf = vec![
+            0;
+            reader
+                .output_buffer_size()
+                .ok_or(anyhow::anyhow!("Icon is too large to decode"))?
+        ];
+        let frame = reader.next_frame(&mut buf)?;
+        let pixels = &buf[..frame.buffer_size()];
+
+        let rgba = match reader.output_color_type() {
+            (ColorType::Rgba, BitDepth::Eight) => pixels.to_vec(),
+            (ColorType::Rgb, BitDepth::Eight) => pixels
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 0xff])
+                .collect(),
+            (ColorType::GrayscaleAlpha, BitDepth::Eight) => pixels
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            (ColorType::Grayscale, BitDepth::Eight) => {
+                pixels.iter().flat_map(|&p| [p, p, p, 0xff]).collect()
+            }
+            (color, depth) => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported icon pixel format: {:?} {:?}",
+                    color,
+                    depth
+                ));
+            }
+        };
+        Ok(Self {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    /// Scales the icon to fit in a `size`x`size` square, keeping its aspect ratio. `size` is
+    /// clamped to `1..=MAX_ICON_DIMENSION`. Each output pixel averages the source pixels it
+    /// covers, weighted by alpha.
+    pub fn scale_to_fit(&self, size: u32) -> Icon {
+        let size = size.clamp(1, MAX_ICON_DIMENSION);
+        let longest = self.width.max(self.height) as u64;
+        let width = ((self.width as u64 * size as u64 + longest / 2) / longest).max(1) as u32;
+        let height = ((self.height as u64 * size as u64 + longest / 2) / longest).max(1) as u32;
+        if width == self.width && height == self.height {
+            return self.clone();
+        }
+
+        let (src_w, src_h) = (self.width as u64, self.height as u64);
+        let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height as u64 {
+            let y0 = y * src_h / height as u64;
+            let y1 = ((y + 1) * src_h / height as u64).max(y0 + 1);
+            for x in 0..width as u64 {
+                let x0 = x * src_w / width as u64;
+                let x1 = ((x + 1) * src_w / width as u64).max(x0 + 1);
+                let mut sum = [0u64; 4];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let i = ((sy * src_w + sx) * 4) as usize;
+                        let alpha = self.rgba[i + 3] as u64;
+                        sum[0] += self.rgba[i] as u64 * alpha;
+                        sum[1] += self.rgba[i + 1] as u64 * alpha;
+                        sum[2] += self.rgba[i + 2] as u64 * alpha;
+                        sum[3] += alpha;
+                    }
+                }
+                let count = (y1 - y0) * (x1 - x0);
+                match NonZeroU64::new(sum[3]) {
+                    None => rgba.extend([0, 0, 0, 0]),
+                    Some(alpha) => rgba.extend([
+                        (sum[0] / alpha) as u8,
+                        (sum[1] / alpha) as u8,
+                        (sum[2] / alpha) as u8,
+                        (sum[3] / count) as u8,
+                    ]),
+                }
+            }
+        }
+        Icon {
+            width,
+            height,
+            rgba,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icon(width: u32, height: u32, pixels: &[[u8; 4]]) -> Icon {
+        Icon {
+            width,
+            height,
+            rgba: pixels.concat(),
+        }
+    }
+
+    #[test]
+    fn averages_covered_pixels_by_alpha() {
+        let source = icon(2, 2, &[[255, 0, 0, 255], [0, 0, 255, 0], [0; 4], [0; 4]]);
+        let scaled = source.scale_to_fit(1);
+        assert_eq!((scaled.width, scaled.height), (1, 1));
+        assert_eq!(scaled.rgba, [255, 0, 0, 63]);
+    }
+
+    #[test]
+    fn keeps_transparent_pixels_black() {
+        let scaled = icon(2, 2, &[[255, 255, 255, 0]; 4]).scale_to_fit(1);
+        assert_eq!(scaled.rgba, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn keeps_aspect_ratio() {
+        let scaled = icon(4, 2, &[[10, 20, 30, 255]; 8]).scale_to_fit(2);
+        assert_eq!((scaled.width, scaled.height), (2, 1));
+        assert_eq!(scaled.rgba, [10, 20, 30, 255].repeat(2));
+    }
+
+    #[test]
+    fn clamps_size() {
+        let source = icon(2, 1, &[[1, 2, 3, 255]; 2]);
+        let smallest = source.scale_to_fit(0);
+        assert_eq!((smallest.width, smallest.height), (1, 1));
+        let largest = source.scale_to_fit(u32::MAX);
+        assert_eq!(
+            (largest.width, largest.height),
+            (MAX_ICON_DIMENSION, MAX_ICON_DIMENSION / 2)
+        );
+        assert_eq!(
+            largest.rgba.len(),
+            (MAX_ICON_DIMENSION * MAX_ICON_DIMENSION / 2 * 4) as usize
+        );
+    }
+}
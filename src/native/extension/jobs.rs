@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    pin::pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI64, Ordering},
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::Thread,
+    time::{Duration, Instant},
+};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::extension::config::JobSettings;
+
+/// Called from a worker thread when a job finishes, with the job id and its status code.
+pub type JobCallback = unsafe extern "C" fn(job: i64, status: i32);
+
+pub static JOB_RUNNING: i32 = 0;
+pub static JOB_COMPLETED: i32 = 1;
+pub static JOB_FAILED: i32 = 2;
+
+#[derive(Debug)]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed(anyhow::Error),
+}
+
+impl JobStatus {
+    pub fn code(&self) -> i32 {
+        match self {
+            JobStatus::Running => JOB_RUNNING,
+            JobStatus::Completed => JOB_COMPLETED,
+            JobStatus::Failed(_) => JOB_FAILED,
+        }
+    }
+}
+
+/// Native worker pool running extension calls off the thread that drives the runtime.
+/// Finished jobs are kept until their result is collected with [`JobRunner::poll`]; the
+/// optional callback only signals that a job has finished.
+pub struct JobRunner {
+    pool: ThreadPool,
+    next_id: AtomicI64,
+    jobs: Arc<Mutex<HashMap<i64, JobStatus>>>,
+    callback: Arc<Mutex<Option<JobCallback>>>,
+}
+
+impl JobRunner {
+    pub fn new(settings: &JobSettings) -> anyhow::Result<Self> {
+        let threads = match settings.threads {
+            0 => std::thread::available_parallelism()
+                .map_or(1, |cores| cores.get().saturating_sub(1).max(1)),
+            threads => threads,
+        };
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("ark-extension-worker-{}", index))
+            .build()?;
+        Ok(Self {
+            pool,
+            next_id: AtomicI64::new(1),
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            callback: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    pub fn spawn(&self, job: impl FnOnce() -> anyhow::Result<()> + Send + 'static) -> i64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.jobs.lock().unwrap().insert(id, JobStatus::Running);
+        let jobs = self.jobs.clone();
+        let callback = self.callback.clone();
+        self.pool.spawn(move || {
+            let status = match job() {
+                Ok(()) => JobStatus::Completed,
+                Err(err) => JobStatus::Failed(err),
+            };
+            let code = status.code();
+            jobs.lock().unwrap().insert(id, status);
+            if let Some(callback) = *callback.lock().unwrap() {
+                // SAFETY: the host registered the callback as safe to call from any thread.
+                unsafe { callback(id, code) };
+            }
+        });
+        id
+    }
+
+    pub fn set_callback(&self, callback: Option<JobCallback>) {
+        *self.callback.lock().unwrap() = callback;
+    }
+
+    /// Returns the status of `job`, forgetting it once it has finished.
+    pub fn poll(&self, job: i64) -> Option<JobStatus> {
+        let mut jobs = self.jobs.lock().unwrap();
+        match jobs.get(&job)? {
+            JobStatus::Running => Some(JobStatus::Running),
+            _ => jobs.remove(&job),
+        }
+    }
+}
+
+/// Runs `future` to completion on the current thread, parking it while the future waits.
+/// Fails once `timeout` has passed, dropping the future; an async guest call dropped this way
+/// is abandoned at the point where it last yielded.
+pub fn block_on<F: Future>(future: F, timeout: Option<Duration>) -> anyhow::Result<F::Output> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return Ok(output);
+        }
+        match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(anyhow::anyhow!(
+                        "Job timed out after {} ms",
+                        timeout.unwrap_or_default().as_millis()
+                    ));
+                }
+                std::thread::park_timeout(deadline - now);
+            }
+            None => std::thread::park(),
+        }
+    }
+}
+
+/// Wakes the thread blocked in [`block_on`].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
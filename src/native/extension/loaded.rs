@@ -0,0 +1,201 @@
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use wasmtime::Store;
+
+use crate::extension::{
+    metrics::SharedMetrics,
+    package::{ExtensionIdentifier, ExtensionPackage},
+    service::ServiceImport,
+    wasm::{ExtensionContext, ExtensionInstance},
+};
+
+thread_local! {
+    /// Extensions the current thread is inside a guest call of, outermost first.
+    static ACTIVE: RefCell<Vec<ExtensionIdentifier>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A loaded extension with its own lock, so that a guest call only blocks callers of the
+/// same extension. What doesn't change while the extension is loaded is kept outside the
+/// lock and can be read while a guest call is running.
+pub struct LoadedExtension {
+    pub identifier: ExtensionIdentifier,
+    pub package: Arc<ExtensionPackage>,
+    /// Package file in the extension folder this extension was loaded from, if any.
+    pub file_name: Option<String>,
+    /// Id of the extension's store, see [`ExtensionContext::store_id`].
+    pub store_id: u64,
+    /// Imports served by other extensions, which can't be unloaded while this one is loaded.
+    pub services: Vec<ServiceImport>,
+    /// Call metrics of the extension, shared with its store.
+    pub metrics: SharedMetrics,
+    state: Mutex<(Store<ExtensionContext>, ExtensionInstance)>,
+}
+
+impl LoadedExtension {
+    pub fn new(
+        store: Store<ExtensionContext>,
+        instance: ExtensionInstance,
+        file_name: Option<String>,
+    ) -> Self {
+        Self {
+            identifier: store.data().identifier.clone(),
+            package: store.data().package.clone(),
+            file_name,
+            store_id: store.data().store_id,
+            services: store.data().services.clone(),
+            metrics: store.data().metrics.clone(),
+            state: Mutex::new((store, instance)),
+        }
+    }
+
+    /// Locks the extension's store for a guest call, waiting for calls on other threads to
+    /// finish. Fails instead of deadlocking when the current thread is already inside a call
+    /// into this extension, e.g. a host function dispatching back into its caller.
+    ///
+    /// Only the current thread's calls are checked: A calling B on one thread while B calls A
+    /// on another still deadlocks. Service calls, the only guest-to-guest calls, always go
+    /// from an extension to one of its dependencies, and dependencies can't form a cycle, so
+    /// they can't run into this; new host functions calling into other extensions must keep
+    /// to that order too.
+    pub fn lock(&self) -> Result<ExtensionGuard<'_>, ReentrantCall> {
+        let call = ActiveCall::enter(self.identifier.unversioned())?;
+        let guard = self.state.lock().unwrap();
+        Ok(ExtensionGuard { guard, _call: call })
+    }
+}
+
+/// Exclusive access to an extension's store, released when dropped.
+pub struct ExtensionGuard<'a> {
+    guard: MutexGuard<'a, (Store<ExtensionContext>, ExtensionInstance)>,
+    _call: ActiveCall,
+}
+
+impl Deref for ExtensionGuard<'_> {
+    type Target = (Store<ExtensionContext>, ExtensionInstance);
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl DerefMut for ExtensionGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// Entry of an extension in the current thread's `ACTIVE` stack, removed when dropped.
+struct ActiveCall {
+    key: ExtensionIdentifier,
+}
+
+impl ActiveCall {
+    /// Pushes `key` onto the stack, unless the current thread is already inside a call
+    /// into it.
+    fn enter(key: ExtensionIdentifier) -> Result<Self, ReentrantCall> {
+        ACTIVE.with_borrow_mut(|active| {
+            if active.contains(&key) {
+                return Err(ReentrantCall {
+                    identifier: key.to_string(),
+                    stack: active.iter().map(ToString::to_string).collect(),
+                });
+            }
+            active.push(key.clone());
+            Ok(Self { key })
+        })
+    }
+}
+
+impl Drop for ActiveCall {
+    fn drop(&mut self) {
+        ACTIVE.with_borrow_mut(|active| {
+            // Guards may be dropped out of order, so remove this entry, not the top.
+            if let Some(position) = active.iter().rposition(|key| key == &self.key) {
+                active.remove(position);
+            }
+        });
+    }
+}
+
+/// Error returned when guest code calls back into an extension that is already on the
+/// current thread's call stack.
+#[derive(Debug)]
+pub struct ReentrantCall {
+    pub identifier: String,
+    /// Extensions being called on this thread, outermost first.
+    pub stack: Vec<String>,
+}
+
+impl Display for ReentrantCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Re-entrant call into extension {} (call stack: {})",
+            self.identifier,
+            self.stack.join(" -> ")
+        )
+    }
+}
+
+impl std::error::Error for ReentrantCall {}
+
+/// Error returned when a handler is called after the instance that registered it was
+/// reloaded, upgraded or unloaded. Its function belongs to that instance's store and can't be
+/// called on the store of the instance that replaced it.
+#[derive(Debug)]
+pub struct StaleHandler {
+    pub identifier: String,
+}
+
+impl Display for StaleHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Handler of {} belongs to an instance that has since been replaced",
+            self.identifier
+        )
+    }
+}
+
+impl std::error::Error for StaleHandler {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> ExtensionIdentifier {
+        s.parse().unwrap()
+    }
+
+    fn active() -> Vec<String> {
+        ACTIVE.with_borrow(|active| active.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn rejects_nested_calls_into_the_same_extension() {
+        let outer = ActiveCall::enter(key("acme:one")).unwrap();
+        let inner = ActiveCall::enter(key("acme:two")).unwrap();
+        let err = ActiveCall::enter(key("acme:one")).err().unwrap();
+        assert_eq!(err.identifier, "acme:one");
+        assert_eq!(err.stack, ["acme:one", "acme:two"]);
+        drop(inner);
+        drop(outer);
+    }
+
+    #[test]
+    fn releases_entries_when_dropped() {
+        let outer = ActiveCall::enter(key("acme:one")).unwrap();
+        let inner = ActiveCall::enter(key("acme:two")).unwrap();
+        drop(outer);
+        assert_eq!(active(), ["acme:two"]);
+        let again = ActiveCall::enter(key("acme:one")).unwrap();
+        drop(inner);
+        drop(again);
+        assert!(active().is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use wasmtime::{ResourceLimiter, Store, StoreLimits, Trap};
+
+use crate::extension::wasm::ExtensionContext;
+
+/// Trigger name calls to the manifest's entry function are recorded under.
+pub static INITIALIZE_TRIGGER: &str = "initialize";
+/// Trigger name calls to the manifest's close function are recorded under.
+pub static CLOSE_TRIGGER: &str = "close";
+/// Trigger name scheduled callbacks are recorded under.
+pub static SCHEDULER_TRIGGER: &str = "scheduler";
+/// Prefix of the trigger names bus deliveries are recorded under, followed by the channel.
+pub static BUS_TRIGGER_PREFIX: &str = "bus:";
+/// Prefix of the trigger names calls into a provided service are recorded under, followed by
+/// the exported interface.
+pub static SERVICE_TRIGGER_PREFIX: &str = "service:";
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CallMetrics {
+    pub calls: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+    /// Calls that ended in a guest trap, as opposed to returning or failing in the host.
+    pub traps: u64,
+    /// Fuel consumed, only counted when fuel metering is enabled in the host config.
+    pub fuel: u64,
+}
+
+impl CallMetrics {
+    fn record(&mut self, duration: Duration, trapped: bool, fuel: u64) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.calls += 1;
+        self.total_micros = self.total_micros.saturating_add(micros);
+        self.max_micros = self.max_micros.max(micros);
+        self.traps += trapped as u64;
+        self.fuel = self.fuel.saturating_add(fuel);
+    }
+}
+
+/// Call accounting of one extension, overall and by trigger.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ExtensionMetrics {
+    pub total: CallMetrics,
+    pub triggers: BTreeMap<String, CallMetrics>,
+    /// Most linear memory, in bytes, the extension's memories have added up to.
+    pub memory_high_water: u64,
+    #[serde(skip)]
+    memory_current: u64,
+}
+
+impl ExtensionMetrics {
+    pub fn record(&mut self, trigger: &str, duration: Duration, trapped: bool, fuel: u64) {
+        self.total.record(duration, trapped, fuel);
+        self.triggers
+            .entry(trigger.to_string())
+            .or_default()
+            .record(duration, trapped, fuel);
+    }
+
+    /// Clears the call counters and restarts the memory high-water mark from the memory in
+    /// use now.
+    pub fn reset(&mut self) {
+        self.total = CallMetrics::default();
+        self.triggers.clear();
+        self.memory_high_water = self.memory_current;
+    }
+}
+
+pub type SharedMetrics = Arc<Mutex<ExtensionMetrics>>;
+
+/// Runs a guest call on `store` and records it under `trigger`.
+pub fn measure<R>(
+    store: &mut Store<ExtensionContext>,
+    trigger: &str,
+    call: impl FnOnce(&mut Store<ExtensionContext>) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+    let fuel_before = store.get_fuel().ok();
+    let start = Instant::now();
+    let result = call(&mut *store);
+    let duration = start.elapsed();
+    let fuel = fuel_before
+        .zip(store.get_fuel().ok())
+        .map_or(0, |(before, after)| before.saturating_sub(after));
+    let trapped = result.as_ref().is_err_and(is_trap);
+    store
+        .data()
+        .metrics
+        .lock()
+        .unwrap()
+        .record(trigger, duration, trapped, fuel);
+    result
+}
+
+/// Whether a failed guest call trapped in the guest, as opposed to failing in the host. The
+/// trap stays reachable through the conversion of wasmtime's error into `anyhow` and any
+/// context added on top of it.
+pub fn is_trap(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<Trap>().is_some()
+}
+
+/// Store limiter that enforces the extension's limits and tracks how much linear memory
+/// its memories add up to.
+pub struct MeteredLimits {
+    limits: StoreLimits,
+    metrics: SharedMetrics,
+}
+
+impl MeteredLimits {
+    pub fn new(limits: StoreLimits, metrics: SharedMetrics) -> Self {
+        Self { limits, metrics }
+    }
+}
+
+impl ResourceLimiter for MeteredLimits {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        let allowed = self.limits.memory_growing(current, desired, maximum)?;
+        if allowed {
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.memory_current += desired.saturating_sub(current) as u64;
+            metrics.memory_high_water = metrics.memory_high_water.max(metrics.memory_current);
+        }
+        Ok(allowed)
+    }
+
+    fn memory_grow_failed(&mut self, error: wasmtime::Error) -> wasmtime::Result<()> {
+        self.limits.memory_grow_failed(error)
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+
+    fn table_grow_failed(&mut self, error: wasmtime::Error) -> wasmtime::Result<()> {
+        self.limits.table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::testing::unreachable_error;
+
+    #[test]
+    fn counts_guest_traps() {
+        let trap = unreachable_error();
+        let host_failure = anyhow::anyhow!("host function failed");
+        let mut metrics = ExtensionMetrics::default();
+        metrics.record(
+            INITIALIZE_TRIGGER,
+            Duration::from_millis(3),
+            is_trap(&trap),
+            0,
+        );
+        metrics.record(
+            INITIALIZE_TRIGGER,
+            Duration::from_millis(1),
+            is_trap(&host_failure),
+            0,
+        );
+        assert_eq!(metrics.total.calls, 2);
+        assert_eq!(metrics.total.traps, 1);
+        assert_eq!(metrics.total.max_micros, 3000);
+        assert_eq!(metrics.triggers[INITIALIZE_TRIGGER].traps, 1);
+    }
+
+    #[test]
+    fn finds_traps_under_context() {
+        assert!(is_trap(
+            &unreachable_error().context("Failed to initialize extension")
+        ));
+    }
+
+    #[test]
+    fn reset_clears_calls() {
+        let mut metrics = ExtensionMetrics::default();
+        metrics.record(CLOSE_TRIGGER, Duration::from_millis(1), false, 5);
+        metrics.reset();
+        assert_eq!(metrics.total.calls, 0);
+        assert!(metrics.triggers.is_empty());
+    }
+}
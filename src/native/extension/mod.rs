@@ -1,3 +1,25 @@
+pub mod binding;
+pub mod bus;
+pub mod cache;
+pub mod compat;
+pub mod config;
+pub mod crash;
+pub mod debug;
+pub mod epoch;
+pub mod i18n;
+pub mod icon;
+pub mod jobs;
+pub mod loaded;
+pub mod metrics;
+pub mod module;
 pub mod package;
+pub mod policy;
+pub mod pool;
+pub mod profiling;
+pub mod safe_mode;
+pub mod scheduler;
+pub mod service;
+#[cfg(test)]
+mod testing;
 pub mod wasm;
-pub mod binding;
+pub mod watch;
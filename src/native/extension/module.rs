@@ -0,0 +1,251 @@
+use wasmtime::{Caller, Instance, Linker, Module, Store, TypedFunc};
+
+use crate::extension::{
+    binding::ark::core::{
+        host::Host,
+        logging::{self, Level},
+    },
+    wasm::{ExtensionContext, RegisteredFunc, Registration},
+};
+
+/// Import module name under which core wasm modules find the `ark:core` functions.
+/// Strings are passed as `(ptr, len)` pairs into the module's exported `memory`.
+static CORE_MODULE: &str = "ark:core";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryKind {
+    Component,
+    Module,
+}
+
+pub fn detect_binary_kind(bytes: &[u8]) -> anyhow::Result<BinaryKind> {
+    match bytes.get(..8) {
+        Some([0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00]) => Ok(BinaryKind::Module),
+        Some([0x00, 0x61, 0x73, 0x6d, _, _, 0x01, 0x00]) => Ok(BinaryKind::Component),
+        _ => Err(anyhow::anyhow!(
+            "Entrypoint is neither a wasm component nor a core wasm module"
+        )),
+    }
+}
+
+pub(crate) fn add_to_linker(linker: &mut Linker<ExtensionContext>) -> wasmtime::Result<()> {
+    wasmtime_wasi::p1::add_to_linker_sync(linker, |data: &mut ExtensionContext| {
+        data.wasi_p1
+            .as_mut()
+            .expect("core module store without a preview1 context")
+    })?;
+    linker.func_wrap(
+        CORE_MODULE,
+        "log",
+        |mut caller: Caller<'_, ExtensionContext>,
+         level: i32,
+         ptr: i32,
+         len: i32|
+         -> wasmtime::Result<()> {
+            let message = read_string(&mut caller, ptr, len)?;
+            logging::Host::log(caller.data_mut(), to_level(level)?, message);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        CORE_MODULE,
+        "is-enabled",
+        |mut caller: Caller<'_, ExtensionContext>, level: i32| -> wasmtime::Result<i32> {
+            Ok(logging::Host::is_enabled(caller.data_mut(), to_level(level)?) as i32)
+        },
+    )?;
+    linker.func_wrap(
+        CORE_MODULE,
+        "check-vulkan-feature",
+        |mut caller: Caller<'_, ExtensionContext>, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+            let feature = read_string(&mut caller, ptr, len)?;
+            Ok(caller.data_mut().check_vulkan_feature(feature) as i32)
+        },
+    )?;
+    linker.func_wrap(
+        CORE_MODULE,
+        "check-vulkan-extension",
+        |mut caller: Caller<'_, ExtensionContext>, ptr: i32, len: i32| -> wasmtime::Result<i32> {
+            let extension = read_string(&mut caller, ptr, len)?;
+            Ok(caller.data_mut().check_vulkan_extension(extension) as i32)
+        },
+    )?;
+    linker.func_wrap(
+        CORE_MODULE,
+        "register",
+        |mut caller: Caller<'_, ExtensionContext>,
+         trigger_ptr: i32,
+         trigger_len: i32,
+         function_ptr: i32,
+         function_len: i32|
+         -> wasmtime::Result<i32> {
+            let trigger = read_string(&mut caller, trigger_ptr, trigger_len)?;
+            let function = read_string(&mut caller, function_ptr, function_len)?;
+            match register(&mut caller, trigger, function) {
+                Ok(()) => Ok(0),
+                Err(err) => {
+                    log::warn!(
+                        "[ark-ext-{}] register failed: {}",
+                        caller.data().package.manifest.id,
+                        err
+                    );
+                    Ok(1)
+                }
+            }
+        },
+    )?;
+    Ok(())
+}
+
+/// Instantiates a core module, reporting every import the linker can't satisfy up front,
+/// and runs the preview1 reactor initializer if the module exports one.
+pub(crate) fn instantiate<T>(
+    linker: &Linker<T>,
+    store: &mut Store<T>,
+    module: &Module,
+) -> anyhow::Result<Instance> {
+    let missing: Vec<String> = module
+        .imports()
+        .filter(|import| linker.get_by_import(&mut *store, import).is_none())
+        .map(|import| format!("{}::{} ({:?})", import.module(), import.name(), import.ty()))
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Core module requires unsupported imports: {}",
+            missing.join(", ")
+        ));
+    }
+    let instance = linker.instantiate(&mut *store, module)?;
+    if let Some(init) = instance.get_func(&mut *store, "_initialize") {
+        init.call(&mut *store, &[], &mut [])?;
+    }
+    Ok(instance)
+}
+
+fn register(
+    caller: &mut Caller<'_, ExtensionContext>,
+    trigger: String,
+    function: String,
+) -> wasmtime::Result<()> {
+    let fun: TypedFunc<(), ()> = caller
+        .get_export(&function)
+        .and_then(|export| export.into_func())
+        .ok_or(wasmtime::Error::msg(format!(
+            "Function not exported: {}",
+            function
+        )))?
+        .typed(&*caller)?;
+    let data = caller.data();
+    let registration = Registration {
+        func: RegisteredFunc::Module(fun),
+        owner: data.identifier.unversioned(),
+        store_id: data.store_id,
+        affinity: data.package.manifest.handler_affinity(&function),
+    };
+    data.public_registry
+        .lock()
+        .map_err(|err| wasmtime::Error::msg(err.to_string()))?
+        .insert(trigger, registration);
+    Ok(())
+}
+
+fn read_string(
+    caller: &mut Caller<'_, ExtensionContext>,
+    ptr: i32,
+    len: i32,
+) -> wasmtime::Result<String> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or(wasmtime::Error::msg("Module does not export its memory"))?;
+    let bytes = memory
+        .data(&*caller)
+        .get(ptr as u32 as usize..)
+        .and_then(|data| data.get(..len as u32 as usize))
+        .ok_or(wasmtime::Error::msg(
+            "String out of bounds of module memory",
+        ))?;
+    Ok(std::str::from_utf8(bytes)?.to_string())
+}
+
+fn to_level(level: i32) -> wasmtime::Result<Level> {
+    Ok(match level {
+        0 => Level::Trace,
+        1 => Level::Debug,
+        2 => Level::Info,
+        3 => Level::Warn,
+        4 => Level::Error,
+        _ => {
+            return Err(wasmtime::Error::msg(format!(
+                "Invalid log level: {}",
+                level
+            )));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::Engine;
+
+    use super::*;
+
+    /// Preamble of a component binary: the wasm magic followed by the component layer.
+    static COMPONENT_HEADER: &[u8] = &[0x00, 0x61, 0x73, 0x6d, 0x0d, 0x00, 0x01, 0x00];
+
+    #[test]
+    fn detects_binary_kinds() {
+        assert_eq!(
+            detect_binary_kind(&[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00]).unwrap(),
+            BinaryKind::Module
+        );
+        assert_eq!(
+            detect_binary_kind(COMPONENT_HEADER).unwrap(),
+            BinaryKind::Component
+        );
+        assert!(detect_binary_kind(b"\0asm").is_err());
+        assert!(detect_binary_kind(b"(module)").is_err());
+    }
+
+    #[test]
+    fn reports_every_missing_import() {
+        let engine = Engine::default();
+        let module = Module::new(
+            &engine,
+            r#"(module
+                (import "ark:core" "log" (func (param i32 i32 i32)))
+                (import "env" "missing" (func)))"#,
+        )
+        .unwrap();
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap(CORE_MODULE, "log", |_: i32, _: i32, _: i32| {})
+            .unwrap();
+        let mut store = Store::new(&engine, ());
+        let err = instantiate(&Linker::new(&engine), &mut store, &module).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("ark:core::log"), "{}", message);
+        assert!(message.contains("env::missing"), "{}", message);
+
+        let err = instantiate(&linker, &mut store, &module).unwrap_err();
+        let message = err.to_string();
+        assert!(!message.contains("ark:core::log"), "{}", message);
+        assert!(message.contains("env::missing"), "{}", message);
+    }
+
+    #[test]
+    fn runs_reactor_initializer() {
+        let engine = Engine::default();
+        let module = Module::new(
+            &engine,
+            r#"(module
+                (global $initialized (export "initialized") (mut i32) (i32.const 0))
+                (func (export "_initialize") (global.set $initialized (i32.const 1))))"#,
+        )
+        .unwrap();
+        let mut store = Store::new(&engine, ());
+        let instance = instantiate(&Linker::new(&engine), &mut store, &module).unwrap();
+        let initialized = instance.get_global(&mut store, "initialized").unwrap();
+        assert_eq!(initialized.get(&mut store).i32(), Some(1));
+    }
+}
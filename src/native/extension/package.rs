@@ -1,31 +1,44 @@
 use std::{
     borrow::Cow,
+    cmp::Ordering,
     collections::HashMap,
     fmt::Display,
-    io::{Cursor, Read},
+    io::{Cursor, Read, Seek},
+    str::FromStr,
 };
 
+use semver::{Version, VersionReq};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::extension::i18n::{DESCRIPTION_KEY, NAME_KEY, Translations};
 
 static MANIFEST_FILE: &str = "manifest.json";
+static DIGESTS_FILE: &str = "digests.json";
 
 pub fn parse_package(zip: &[u8]) -> anyhow::Result<ExtensionPackage> {
+    let hash = PackageHash::of(zip);
     let mut archive = zip::ZipArchive::new(Cursor::new(zip))?;
+    let manifest_content = read_manifest_content(&mut archive)?;
 
-    let manifest = {
-        let mut manifest_file = archive
-            .by_name(MANIFEST_FILE)
-            .map_err(|e| anyhow::anyhow!("Failed to find {}: {}", MANIFEST_FILE, e))?;
-        let mut content = String::new();
-        manifest_file.read_to_string(&mut content)?;
-        serde_json::from_str(&content)?
+    let digests: Option<HashMap<String, String>> = match archive.by_name(DIGESTS_FILE) {
+        Ok(mut digests_file) => {
+            let mut content = String::new();
+            digests_file.read_to_string(&mut content)?;
+            Some(
+                serde_json::from_str(&content)
+                    .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", DIGESTS_FILE, e))?,
+            )
+        }
+        Err(zip::result::ZipError::FileNotFound) => None,
+        Err(e) => return Err(e.into()),
     };
 
     let mut files = HashMap::new();
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
         let name = file.name().to_string();
-        if name == MANIFEST_FILE {
+        if name == MANIFEST_FILE || name == DIGESTS_FILE {
             continue;
         }
         let mut data = Vec::new();
@@ -33,13 +46,126 @@ pub fn parse_package(zip: &[u8]) -> anyhow::Result<ExtensionPackage> {
         files.insert(name, Cow::Owned(data));
     }
 
-    Ok(ExtensionPackage { manifest, files })
+    if let Some(digests) = &digests {
+        verify_digests(digests, manifest_content.as_bytes(), &files)?;
+    }
+    let manifest = serde_json::from_str(&manifest_content)?;
+    let translations = Translations::from_files(&files)?;
+
+    Ok(ExtensionPackage {
+        manifest,
+        translations,
+        files,
+        hash,
+        verified: digests.is_some(),
+    })
+}
+
+/// Reads only the manifest of a package, without loading or verifying its other files.
+pub fn read_manifest(zip: impl Read + Seek) -> anyhow::Result<ExtensionManifest> {
+    let mut archive = zip::ZipArchive::new(zip)?;
+    Ok(serde_json::from_str(&read_manifest_content(&mut archive)?)?)
+}
+
+fn read_manifest_content(
+    archive: &mut zip::ZipArchive<impl Read + Seek>,
+) -> anyhow::Result<String> {
+    let mut manifest_file = archive
+        .by_name(MANIFEST_FILE)
+        .map_err(|e| anyhow::anyhow!("Failed to find {}: {}", MANIFEST_FILE, e))?;
+    let mut content = String::new();
+    manifest_file.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Checks every file of the package against the SHA-256 list from `digests.json`.
+/// Once a package ships a digest list, every file (including `manifest.json`) must be
+/// listed in it, so files added after release are rejected as well as modified ones.
+fn verify_digests(
+    digests: &HashMap<String, String>,
+    manifest: &[u8],
+    files: &HashMap<String, Cow<'static, [u8]>>,
+) -> anyhow::Result<()> {
+    let entries = std::iter::once((MANIFEST_FILE, manifest)).chain(
+        files
+            .iter()
+            .filter(|(name, _)| !name.ends_with('/'))
+            .map(|(name, data)| (name.as_str(), data.as_ref())),
+    );
+    for (name, data) in entries {
+        let expected = digests.get(name).ok_or(anyhow::anyhow!(
+            "File '{}' is not listed in {}",
+            name,
+            DIGESTS_FILE
+        ))?;
+        let actual = PackageHash::of(data).to_string();
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(anyhow::anyhow!(
+                "Digest mismatch for '{}': expected {}, got {}",
+                name,
+                expected,
+                actual
+            ));
+        }
+    }
+    for name in digests.keys() {
+        if name != MANIFEST_FILE && !files.contains_key(name) {
+            return Err(anyhow::anyhow!(
+                "File '{}' listed in {} is missing from the package",
+                name,
+                DIGESTS_FILE
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// SHA-256 of a whole package archive, or of a single file inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PackageHash(pub [u8; 32]);
+
+impl PackageHash {
+    pub fn of(bytes: &[u8]) -> Self {
+        Self(Sha256::digest(bytes).into())
+    }
+}
+
+impl Display for PackageHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 pub struct ExtensionPackage {
     pub manifest: ExtensionManifest,
+    pub translations: Translations,
     pub files: HashMap<String, Cow<'static, [u8]>>,
+    /// Hash of the package archive as it was read from disk.
+    pub hash: PackageHash,
+    /// Whether the package shipped a digest list that all of its files matched.
+    pub verified: bool,
+}
+
+impl ExtensionPackage {
+    /// Name shown to the user: the `extension.name` translation, the manifest name, or the id.
+    pub fn localized_name(&self, locale: &str) -> String {
+        self.translations
+            .translate(locale, NAME_KEY)
+            .or(self.manifest.name.as_deref())
+            .unwrap_or(&self.manifest.id)
+            .to_string()
+    }
+
+    pub fn localized_description(&self, locale: &str) -> Option<String> {
+        self.translations
+            .translate(locale, DESCRIPTION_KEY)
+            .or(self.manifest.description.as_deref())
+            .map(str::to_string)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -52,6 +178,27 @@ pub struct ExtensionManifest {
     #[serde(default = "default_entry_function")]
     pub entry_function: String,
     pub close_function: Option<String>,
+    /// Component export called with the previous version string when the extension is
+    /// upgraded in place.
+    pub migrate_function: Option<String>,
+    /// `.cwasm` artifacts precompiled from the entrypoint, tried in order before compiling
+    /// it. Only loaded when the host trusts precompiled code and the artifact matches the
+    /// running engine.
+    #[serde(default)]
+    pub precompiled: Vec<String>,
+    /// Extensions this one needs loaded first, as `namespace:id@range` requirements.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Interfaces the component exports for extensions depending on it, by full export name,
+    /// e.g. `acme:waypoints/api@1.0.0`.
+    #[serde(default)]
+    pub provides: Vec<String>,
+    /// Thread affinity of registered handlers by exported function name; handlers not
+    /// listed only run on the render thread.
+    #[serde(default)]
+    pub handlers: HashMap<String, HandlerAffinity>,
+    #[serde(default)]
+    pub bus: BusPermissions,
     #[serde(default)]
     pub runtime: RuntimeArgs,
     pub name: Option<String>,
@@ -65,18 +212,78 @@ pub struct ExtensionManifest {
     pub custom: Option<HashMap<String, String>>,
 }
 
+/// Where a registered handler may be invoked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HandlerAffinity {
+    /// Only on the thread driving the extensions through FFI, usually the render thread.
+    #[default]
+    Render,
+    /// Also as a background job on the native worker pool.
+    Background,
+}
+
+impl ExtensionManifest {
+    pub fn handler_affinity(&self, function: &str) -> HandlerAffinity {
+        self.handlers.get(function).copied().unwrap_or_default()
+    }
+
+    pub fn requirements(&self) -> anyhow::Result<Vec<ExtensionRequirement>> {
+        self.dependencies
+            .iter()
+            .map(|dependency| {
+                dependency
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid dependency '{}': {}", dependency, e))
+            })
+            .collect()
+    }
+}
+
 fn default_entry_function() -> String {
     "initialize".to_string()
 }
 
+static DEFAULT_NAMESPACE: &str = "ark:unnamed";
+
 fn default_namespace() -> String {
-    "ark:unnamed".to_string()
+    DEFAULT_NAMESPACE.to_string()
 }
 
 fn default_entrypoint() -> String {
     "script".to_string()
 }
 
+/// Bus channels an extension may publish to and subscribe to. A pattern ending in `*`
+/// matches every channel starting with the rest of it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BusPermissions {
+    pub publish: Vec<String>,
+    pub subscribe: Vec<String>,
+}
+
+impl BusPermissions {
+    pub fn may_publish(&self, channel: &str) -> bool {
+        self.publish
+            .iter()
+            .any(|pattern| channel_matches(pattern, channel))
+    }
+
+    pub fn may_subscribe(&self, channel: &str) -> bool {
+        self.subscribe
+            .iter()
+            .any(|pattern| channel_matches(pattern, channel))
+    }
+}
+
+fn channel_matches(pattern: &str, channel: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => channel.starts_with(prefix),
+        None => pattern == channel,
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RuntimeArgs {
     #[serde(default = "default_required_vulkan_version")]
@@ -93,6 +300,12 @@ pub struct RuntimeArgs {
     pub optional_vulkan_features: Vec<String>,
     #[serde(default)]
     pub optional_wasi_features: Vec<String>,
+    /// Largest linear memory, in bytes, the extension may grow to.
+    pub max_memory: Option<u64>,
+    /// Whether the extension is compiled for source-level debugging, see
+    /// [`crate::extension::debug`].
+    #[serde(default)]
+    pub debug: bool,
 }
 
 impl Default for RuntimeArgs {
@@ -105,6 +318,8 @@ impl Default for RuntimeArgs {
             required_vulkan_features: vec![],
             optional_vulkan_features: vec![],
             optional_wasi_features: vec![],
+            max_memory: None,
+            debug: false,
         }
     }
 }
@@ -117,6 +332,9 @@ fn default_required_ark_version() -> String {
     "0.1.0".to_string()
 }
 
+/// `namespace:id@version`. Equality, hashing and ordering compare versions semantically when
+/// both parse as semver, so `1.0` and `1.0.0` are the same version.
+#[derive(Debug, Clone)]
 pub struct ExtensionIdentifier {
     pub id: String,
     pub namespace: String,
@@ -170,6 +388,162 @@ impl ExtensionIdentifier {
             manifest.version.as_deref(),
         )
     }
+
+    /// The identity without its version, which is what the runtime keys extensions by.
+    pub fn unversioned(&self) -> Self {
+        Self::new(&self.id, &self.namespace, None)
+    }
+
+    pub fn semver(&self) -> Option<Version> {
+        self.version.as_deref().and_then(parse_lenient_version)
+    }
+
+    /// Whether the version satisfies `req`. Missing or non-semver versions never match.
+    pub fn satisfies(&self, req: &VersionReq) -> bool {
+        self.semver().is_some_and(|version| req.matches(&version))
+    }
+
+    pub fn same_identity(&self, other: &ExtensionIdentifier) -> bool {
+        self.namespace == other.namespace && self.id == other.id
+    }
+
+    /// `namespace-id`, with characters that aren't safe in file names on every platform
+    /// replaced by `_`, for files written about the extension.
+    pub fn file_stem(&self) -> String {
+        format!("{}-{}", self.namespace, self.id)
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                _ => '_',
+            })
+            .collect()
+    }
+}
+
+/// Parses `1`, `1.2` and `1.2.3` style versions, padding missing components with zeros.
+fn parse_lenient_version(version: &str) -> Option<Version> {
+    Version::parse(version).ok().or_else(|| {
+        let (core, rest) = match version.find(['-', '+']) {
+            Some(pos) => version.split_at(pos),
+            None => (version, ""),
+        };
+        let padding = match core.split('.').count() {
+            1 => ".0.0",
+            2 => ".0",
+            _ => return None,
+        };
+        Version::parse(&format!("{}{}{}", core, padding, rest)).ok()
+    })
+}
+
+fn split_name(name: &str) -> anyhow::Result<(&str, &str)> {
+    let (namespace, id) = name.rsplit_once(':').unwrap_or((DEFAULT_NAMESPACE, name));
+    if namespace.is_empty() || id.is_empty() {
+        return Err(anyhow::anyhow!("Invalid extension identifier: {}", name));
+    }
+    Ok((namespace, id))
+}
+
+impl FromStr for ExtensionIdentifier {
+    type Err = anyhow::Error;
+
+    /// Parses `namespace:id@version`; the version is optional and a name without a
+    /// namespace gets the default `ark:unnamed` one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, version) = match s.split_once('@') {
+            Some((name, version)) if !version.is_empty() => (name, Some(version)),
+            Some(_) => return Err(anyhow::anyhow!("Empty version in identifier: {}", s)),
+            None => (s, None),
+        };
+        let (namespace, id) = split_name(name)?;
+        Ok(Self::new(id, namespace, version))
+    }
+}
+
+impl PartialEq for ExtensionIdentifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ExtensionIdentifier {}
+
+impl std::hash::Hash for ExtensionIdentifier {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.namespace.hash(state);
+        self.id.hash(state);
+        match self.semver() {
+            Some(version) => version.hash(state),
+            None => self.version.hash(state),
+        }
+    }
+}
+
+impl PartialOrd for ExtensionIdentifier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ExtensionIdentifier {
+    /// Orders by namespace, then id, then version; unversioned identifiers sort first and
+    /// semver versions sort before free-form ones.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.namespace
+            .cmp(&other.namespace)
+            .then_with(|| self.id.cmp(&other.id))
+            .then_with(|| match (&self.version, &other.version) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Less,
+                (Some(_), None) => Ordering::Greater,
+                (Some(a), Some(b)) => match (self.semver(), other.semver()) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => a.cmp(b),
+                },
+            })
+    }
+}
+
+/// `namespace:id@range`, e.g. `ark:minimap@^1.2`. A requirement without a range matches
+/// every version of the extension.
+#[derive(Debug, Clone)]
+pub struct ExtensionRequirement {
+    pub id: String,
+    pub namespace: String,
+    pub version: VersionReq,
+}
+
+impl ExtensionRequirement {
+    pub fn matches(&self, identifier: &ExtensionIdentifier) -> bool {
+        self.namespace == identifier.namespace
+            && self.id == identifier.id
+            && (self.version == VersionReq::STAR || identifier.satisfies(&self.version))
+    }
+}
+
+impl FromStr for ExtensionRequirement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, version) = match s.split_once('@') {
+            Some((name, range)) => (name, VersionReq::parse(range)?),
+            None => (s, VersionReq::STAR),
+        };
+        let (namespace, id) = split_name(name)?;
+        Ok(Self {
+            id: id.to_string(),
+            namespace: namespace.to_string(),
+            version,
+        })
+    }
+}
+
+impl Display for ExtensionRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}@{}", self.namespace, self.id, self.version)
+    }
 }
 
 impl Display for ExtensionIdentifier {
@@ -181,3 +555,111 @@ impl Display for ExtensionIdentifier {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_files(files: &[(&str, &[u8])]) -> HashMap<String, Cow<'static, [u8]>> {
+        files
+            .iter()
+            .map(|(name, data)| (name.to_string(), Cow::Owned(data.to_vec())))
+            .collect()
+    }
+
+    fn digests_of(files: &[(&str, &[u8])]) -> HashMap<String, String> {
+        files
+            .iter()
+            .map(|(name, data)| (name.to_string(), PackageHash::of(data).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn accepts_listed_files() {
+        let manifest: &[u8] = b"{}";
+        let files = package_files(&[("main.wasm", b"wasm"), ("assets/", b"")]);
+        let mut digests = digests_of(&[(MANIFEST_FILE, manifest), ("main.wasm", b"wasm")]);
+        verify_digests(&digests, manifest, &files).unwrap();
+        for digest in digests.values_mut() {
+            *digest = digest.to_uppercase();
+        }
+        verify_digests(&digests, manifest, &files).unwrap();
+    }
+
+    #[test]
+    fn rejects_modified_files() {
+        let manifest: &[u8] = b"{}";
+        let files = package_files(&[("main.wasm", b"patched")]);
+        let digests = digests_of(&[(MANIFEST_FILE, manifest), ("main.wasm", b"wasm")]);
+        assert!(verify_digests(&digests, manifest, &files).is_err());
+        let digests = digests_of(&[(MANIFEST_FILE, b"[]"), ("main.wasm", b"patched")]);
+        assert!(verify_digests(&digests, manifest, &files).is_err());
+    }
+
+    #[test]
+    fn rejects_unlisted_files() {
+        let manifest: &[u8] = b"{}";
+        let files = package_files(&[("main.wasm", b"wasm"), ("extra.wasm", b"extra")]);
+        let digests = digests_of(&[(MANIFEST_FILE, manifest), ("main.wasm", b"wasm")]);
+        assert!(verify_digests(&digests, manifest, &files).is_err());
+        let digests = digests_of(&[("main.wasm", b"wasm"), ("extra.wasm", b"extra")]);
+        assert!(verify_digests(&digests, manifest, &files).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_files() {
+        let manifest: &[u8] = b"{}";
+        let files = package_files(&[("main.wasm", b"wasm")]);
+        let digests = digests_of(&[
+            (MANIFEST_FILE, manifest),
+            ("main.wasm", b"wasm"),
+            ("removed.wasm", b"removed"),
+        ]);
+        assert!(verify_digests(&digests, manifest, &files).is_err());
+    }
+
+    fn identifier(s: &str) -> ExtensionIdentifier {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_identifiers() {
+        let full = identifier("acme:tools@1.2.0");
+        assert_eq!(full.namespace, "acme");
+        assert_eq!(full.id, "tools");
+        assert_eq!(full.version.as_deref(), Some("1.2.0"));
+        assert_eq!(full.to_string(), "acme:tools@1.2.0");
+        let bare = identifier("tools");
+        assert_eq!(bare.namespace, DEFAULT_NAMESPACE);
+        assert_eq!(bare.version, None);
+        assert_eq!(identifier("ark:unnamed:tools").namespace, "ark:unnamed");
+        for invalid in ["acme:tools@", ":tools", "acme:", "@1.0.0", ""] {
+            assert!(invalid.parse::<ExtensionIdentifier>().is_err());
+        }
+    }
+
+    #[test]
+    fn compares_versions_semantically() {
+        assert_eq!(identifier("acme:tools@1.0"), identifier("acme:tools@1.0.0"));
+        assert_ne!(identifier("acme:tools@1.0.0"), identifier("acme:tools"));
+        let mut identifiers = [
+            "acme:tools@nightly",
+            "acme:tools@1.10.0",
+            "acme:tools@1.9.0",
+            "acme:tools",
+            "acme:base@2.0.0",
+        ]
+        .map(identifier);
+        identifiers.sort();
+        assert_eq!(
+            identifiers.map(|identifier| identifier.to_string()),
+            [
+                "acme:base@2.0.0",
+                "acme:tools",
+                "acme:tools@1.9.0",
+                "acme:tools@1.10.0",
+                "acme:tools@nightly",
+            ]
+        );
+    }
+}
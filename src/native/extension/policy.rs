@@ -0,0 +1,235 @@
+use std::{fmt::Display, path::Path};
+
+use semver::VersionReq;
+use serde::Deserialize;
+
+use crate::extension::package::{ExtensionIdentifier, PackageHash};
+
+pub static POLICY_FILE: &str = "policy.json";
+
+/// Allow/block list maintained by the host, checked before an extension is compiled or
+/// instantiated. Block rules always win over allow rules; extensions matched by neither
+/// fall back to `default`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ExtensionPolicy {
+    #[serde(default)]
+    pub default: PolicyAction,
+    /// Rejects packages without a digest list, which would otherwise load unverified.
+    #[serde(default)]
+    pub require_verified: bool,
+    #[serde(default)]
+    pub allow: Vec<PolicyRule>,
+    #[serde(default)]
+    pub block: Vec<PolicyRule>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// A rule matches when every criterion it specifies matches; a rule without criteria
+/// matches every extension.
+#[derive(Debug, Deserialize)]
+pub struct PolicyRule {
+    /// `namespace:id` pattern, `*` matches any run of characters.
+    pub id: Option<String>,
+    pub version: Option<VersionReq>,
+    pub hash: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl ExtensionPolicy {
+    /// Reads the policy file from `folder`, or returns an allow-all policy if there is none.
+    pub fn load(folder: &Path) -> anyhow::Result<Self> {
+        let path = folder.join(POLICY_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    pub fn check(
+        &self,
+        identifier: &ExtensionIdentifier,
+        hash: &PackageHash,
+        verified: bool,
+    ) -> Result<(), PolicyViolation> {
+        let violation = |reason: String| PolicyViolation {
+            identifier: identifier.to_string(),
+            hash: *hash,
+            reason,
+        };
+        if self.require_verified && !verified {
+            return Err(violation("package has no digest list".to_string()));
+        }
+        if let Some(rule) = self.block.iter().find(|r| r.matches(identifier, hash)) {
+            return Err(violation(
+                rule.reason
+                    .clone()
+                    .unwrap_or_else(|| "blocked by policy".to_string()),
+            ));
+        }
+        if self.allow.iter().any(|r| r.matches(identifier, hash)) {
+            return Ok(());
+        }
+        match self.default {
+            PolicyAction::Allow => Ok(()),
+            PolicyAction::Deny => Err(violation("not in the allow list".to_string())),
+        }
+    }
+}
+
+impl PolicyRule {
+    pub fn matches(&self, identifier: &ExtensionIdentifier, hash: &PackageHash) -> bool {
+        if let Some(pattern) = &self.id {
+            let name = format!("{}:{}", identifier.namespace, identifier.id);
+            if !wildcard_match(pattern, &name) {
+                return false;
+            }
+        }
+        if let Some(req) = &self.version
+            && !identifier.satisfies(req)
+        {
+            return false;
+        }
+        if let Some(expected) = &self.hash
+            && !hash.to_string().eq_ignore_ascii_case(expected)
+        {
+            return false;
+        }
+        true
+    }
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Load error returned when the host policy rejects an extension.
+#[derive(Debug)]
+pub struct PolicyViolation {
+    pub identifier: String,
+    pub hash: PackageHash,
+    pub reason: String,
+}
+
+impl Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Extension {} (package {}) rejected by policy: {}",
+            self.identifier, self.hash, self.reason
+        )
+    }
+}
+
+impl std::error::Error for PolicyViolation {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(json: &str) -> ExtensionPolicy {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// Builds the identifier `namespace:id@version`, the version being optional.
+    fn identifier(name: &str) -> ExtensionIdentifier {
+        let (name, version) = match name.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (name, None),
+        };
+        let (namespace, id) = name.split_once(':').unwrap();
+        ExtensionIdentifier::new(id, namespace, version)
+    }
+
+    fn check(policy: &ExtensionPolicy, name: &str) -> bool {
+        policy
+            .check(&identifier(name), &PackageHash([0; 32]), true)
+            .is_ok()
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("acme:tools", "acme:tools"));
+        assert!(!wildcard_match("acme:tools", "acme:tools2"));
+        assert!(wildcard_match("acme:*", "acme:tools"));
+        assert!(wildcard_match("acme:*", "acme:"));
+        assert!(wildcard_match("*:tools", "acme:tools"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a*b*c", "a-b-b-c"));
+        assert!(!wildcard_match("a*b*c", "a-c-b"));
+        assert!(!wildcard_match("ab*ba", "aba"));
+        assert!(!wildcard_match("acme:*", "other:tools"));
+    }
+
+    #[test]
+    fn block_rules_win() {
+        let policy = policy(
+            r#"{
+                "allow": [{ "id": "acme:*" }],
+                "block": [{ "id": "acme:tools", "version": "<2.0.0" }]
+            }"#,
+        );
+        assert!(!check(&policy, "acme:tools@1.5.0"));
+        assert!(check(&policy, "acme:tools@2.0.0"));
+        assert!(check(&policy, "acme:other@1.0.0"));
+    }
+
+    #[test]
+    fn falls_back_to_default() {
+        let policy = policy(r#"{ "default": "deny", "allow": [{ "id": "acme:*" }] }"#);
+        assert!(check(&policy, "acme:tools@1.0.0"));
+        assert!(!check(&policy, "other:tools@1.0.0"));
+        assert!(check(&ExtensionPolicy::default(), "other:tools@1.0.0"));
+    }
+
+    #[test]
+    fn matches_hashes_case_insensitively() {
+        let hash = PackageHash([0xab; 32]).to_string().to_uppercase();
+        let rule = PolicyRule {
+            id: None,
+            version: None,
+            hash: Some(hash),
+            reason: None,
+        };
+        let identifier = identifier("acme:tools");
+        assert!(rule.matches(&identifier, &PackageHash([0xab; 32])));
+        assert!(!rule.matches(&identifier, &PackageHash([0; 32])));
+    }
+
+    #[test]
+    fn requires_verified_packages() {
+        let policy = policy(r#"{ "require_verified": true }"#);
+        let identifier = identifier("acme:tools");
+        let hash = PackageHash([0; 32]);
+        assert!(policy.check(&identifier, &hash, true).is_ok());
+        assert!(policy.check(&identifier, &hash, false).is_err());
+        assert!(
+            ExtensionPolicy::default()
+                .check(&identifier, &hash, false)
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use wasmtime::PoolingAllocationConfig;
+
+use crate::extension::{config::PoolingSettings, package::read_manifest};
+
+/// Core instances a component may need, including those of WASI adapters.
+static CORE_INSTANCES_PER_COMPONENT: u32 = 16;
+static MEMORIES_PER_COMPONENT: u32 = 4;
+static TABLES_PER_COMPONENT: u32 = 8;
+
+/// Sizes the instance pool from the packages installed in `folder`: two slots per package,
+/// so an extension can be reloaded or upgraded while its old instance is still alive, plus
+/// the configured spare slots, each large enough for the biggest declared memory limit.
+pub fn pooling_config(folder: &Path, settings: &PoolingSettings) -> PoolingAllocationConfig {
+    let mut packages = 0;
+    let mut memory = settings.default_memory;
+    for path in installed_packages(folder) {
+        packages += 1;
+        match File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(read_manifest)
+        {
+            Ok(manifest) => {
+                memory = memory.max(manifest.runtime.max_memory.unwrap_or_default());
+            }
+            Err(err) => warn!("Failed to read the manifest of {}: {}", path.display(), err),
+        }
+    }
+    let memory = memory.min(settings.max_memory);
+    let instances = packages * 2 + settings.spare_instances;
+
+    let mut config = PoolingAllocationConfig::new();
+    config
+        .total_component_instances(instances)
+        .total_core_instances(instances * CORE_INSTANCES_PER_COMPONENT)
+        .total_memories(instances * MEMORIES_PER_COMPONENT)
+        .total_tables(instances * TABLES_PER_COMPONENT)
+        .max_core_instances_per_component(CORE_INSTANCES_PER_COMPONENT)
+        .max_memories_per_component(MEMORIES_PER_COMPONENT)
+        .max_tables_per_component(TABLES_PER_COMPONENT)
+        .max_memory_size(usize::try_from(memory).unwrap_or(usize::MAX));
+    config
+}
+
+fn installed_packages(folder: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "zip"))
+        .collect()
+}
+
+/// Whether an instantiation failed because every slot of the pool is taken.
+pub fn is_pool_exhausted(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| cause.is::<wasmtime::PoolConcurrencyLimitError>())
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmtime::{Config, Engine, InstanceAllocationStrategy, Store, component::Component};
+
+    use super::*;
+
+    /// Extension folder under the system temp directory holding `packages` package files.
+    fn folder(name: &str, packages: usize) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("ark-pool-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        for package in 0..packages {
+            std::fs::write(folder.join(format!("{}.zip", package)), b"not a zip").unwrap();
+        }
+        std::fs::write(folder.join("notes.txt"), b"not a package").unwrap();
+        folder
+    }
+
+    /// Instantiates an empty component in the pooled engine until its pool is exhausted,
+    /// returning how many instances fit and the error of the first that didn't.
+    fn fill_pool(folder: &Path, settings: &PoolingSettings) -> (usize, anyhow::Error) {
+        let mut config = Config::new();
+        config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling_config(
+            folder, settings,
+        )));
+        let engine = Engine::new(&config).unwrap();
+        let component = Component::new(&engine, "(component)").unwrap();
+        let linker = wasmtime::component::Linker::new(&engine);
+        let mut stores = Vec::new();
+        loop {
+            let mut store = Store::new(&engine, ());
+            match linker.instantiate(&mut store, &component) {
+                Ok(_) => stores.push(store),
+                Err(err) => return (stores.len(), err.into()),
+            }
+        }
+    }
+
+    #[test]
+    fn sizes_pool_from_installed_packages() {
+        let settings = PoolingSettings {
+            enabled: true,
+            default_memory: 64 * 1024,
+            max_memory: 64 * 1024,
+            spare_instances: 1,
+        };
+        let (instances, err) = fill_pool(&folder("sized", 2), &settings);
+        assert_eq!(instances, 5);
+        assert!(is_pool_exhausted(&err), "{:?}", err);
+    }
+
+    #[test]
+    fn falls_back_once_exhausted() {
+        let settings = PoolingSettings {
+            enabled: true,
+            default_memory: 64 * 1024,
+            max_memory: 64 * 1024,
+            spare_instances: 1,
+        };
+        let (instances, err) = fill_pool(&folder("tiny", 0), &settings);
+        assert_eq!(instances, 1);
+        assert!(is_pool_exhausted(&err));
+
+        let engine = Engine::default();
+        let component = Component::new(&engine, "(component)").unwrap();
+        let mut store = Store::new(&engine, ());
+        assert!(
+            wasmtime::component::Linker::new(&engine)
+                .instantiate(&mut store, &component)
+                .is_ok()
+        );
+        assert!(!is_pool_exhausted(&anyhow::anyhow!(
+            "Failed to instantiate"
+        )));
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    path::{self, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use wasmtime::{GuestProfiler, Store, StoreContextMut};
+
+use crate::extension::{
+    config::ProfilingSettings,
+    epoch,
+    package::ExtensionIdentifier,
+    wasm::{ExtensionBinary, ExtensionContext},
+};
+
+/// A guest profile being recorded for one extension's store.
+pub struct ActiveProfile {
+    profiler: GuestProfiler,
+    last_sample: Instant,
+}
+
+/// Installs the call hook that feeds a store's profile while one is recording.
+pub fn configure_store(store: &mut Store<ExtensionContext>) {
+    store.call_hook(|mut context, kind| {
+        if let Some(mut profile) = context.data_mut().profile.take() {
+            profile.profiler.call_hook(&context, kind);
+            context.data_mut().profile = Some(profile);
+        }
+        Ok(())
+    });
+}
+
+/// Takes a sample of the profile recording in the store, if any. Called on every epoch tick.
+pub fn sample(context: &mut StoreContextMut<'_, ExtensionContext>) {
+    if let Some(mut profile) = context.data_mut().profile.take() {
+        let now = Instant::now();
+        profile
+            .profiler
+            .sample(&*context, now - profile.last_sample);
+        profile.last_sample = now;
+        context.data_mut().profile = Some(profile);
+    }
+}
+
+/// Starts recording a profile of the extension running in `store`. Function names are
+/// resolved from the name sections of the component's modules.
+pub fn start(
+    store: &mut Store<ExtensionContext>,
+    settings: &ProfilingSettings,
+) -> anyhow::Result<()> {
+    if store.data().profile.is_some() {
+        return Err(anyhow::anyhow!(
+            "Extension {} is already being profiled",
+            store.data().identifier
+        ));
+    }
+    let engine = store.engine().clone();
+    let name = store.data().identifier.to_string();
+    let interval = Duration::from_millis(settings.interval_ms.max(1));
+    let profiler = match &store.data().binary {
+        ExtensionBinary::Component(component) => {
+            GuestProfiler::new_component(&engine, &name, interval, component.clone(), [])?
+        }
+        ExtensionBinary::Module(module) => {
+            GuestProfiler::new(&engine, &name, interval, [(name.clone(), module.clone())])?
+        }
+    };
+    store.data_mut().profile = Some(ActiveProfile {
+        profiler,
+        last_sample: Instant::now(),
+    });
+    store.set_epoch_deadline(1);
+    Ok(())
+}
+
+/// Stops the profile recording in `store` and writes it as a Firefox profiler JSON file,
+/// returning its path.
+pub fn finish(
+    store: &mut Store<ExtensionContext>,
+    settings: &ProfilingSettings,
+) -> anyhow::Result<PathBuf> {
+    let profile = store.data_mut().profile.take().ok_or(anyhow::anyhow!(
+        "Extension {} is not being profiled",
+        store.data().identifier
+    ))?;
+    epoch::reset_deadline(store);
+    let directory = path::absolute(&settings.directory)?;
+    std::fs::create_dir_all(&directory)?;
+    let path = directory.join(profile_file_name(&store.data().identifier));
+    let file = std::fs::File::create(&path)?;
+    profile
+        .profiler
+        .finish(std::io::BufWriter::new(file))
+        .map_err(|err| anyhow::anyhow!("Failed to write profile {}: {}", path.display(), err))?;
+    Ok(path)
+}
+
+fn profile_file_name(identifier: &ExtensionIdentifier) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    format!("{}-{}.json", identifier.file_stem(), timestamp)
+}
@@ -0,0 +1,415 @@
+use std::{
+    fs::{File, TryLockError},
+    path::{self, Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use anyhow::anyhow;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::extension::{config::HostConfig, package::ExtensionIdentifier};
+
+/// Directory holding one marker file per running initializer, each naming its extension.
+/// Markers are removed when the initializer returns; finding one of a process that is no
+/// longer running means that process died inside that initializer.
+static SENTINEL_DIRECTORY: &str = "initializing";
+static SENTINEL_SUFFIX: &str = ".sentinel";
+/// Lock file named after its process id, locked by the process as long as it runs so that
+/// other processes sharing the directory can tell whether its sentinels are still in use.
+static LOCK_SUFFIX: &str = ".lock";
+/// Extensions skipped after such a crash until they are re-enabled.
+static SKIPPED_FILE: &str = "safe-mode.json";
+
+/// Distinguishes the markers of initializers running at the same time.
+static SENTINEL_COUNTER: AtomicU64 = AtomicU64::new(0);
+/// Locks this process holds, by sentinel directory, taken by the first [`SafeMode::open`] on
+/// the directory and kept until the process exits.
+static PROCESS_LOCKS: Mutex<Vec<(PathBuf, File)>> = Mutex::new(Vec::new());
+
+/// Extensions held back after a crash during initialization.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SkippedExtensions {
+    /// Whether every extension is skipped, because the host config asks for that after a crash.
+    pub all: bool,
+    /// Extensions that were initializing when the process crashed.
+    pub suspects: Vec<String>,
+}
+
+/// Crash sentinel around extension initialization, kept in the native cache directory. An
+/// extension that crashes the process while initializing is skipped on the following starts
+/// instead of crashing them too, until it is re-enabled.
+pub struct SafeMode {
+    enabled: bool,
+    directory: PathBuf,
+    skipped: Mutex<SkippedExtensions>,
+}
+
+impl SafeMode {
+    /// Reads the extensions skipped so far and, if earlier runs left sentinels behind, adds
+    /// the extensions they name. Sentinels of processes still running, e.g. another instance
+    /// sharing the cache directory, are left alone.
+    pub fn open(config: &HostConfig) -> anyhow::Result<Self> {
+        let directory = path::absolute(&config.cache.directory)?;
+        let mut skipped: SkippedExtensions = std::fs::read(directory.join(SKIPPED_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let sentinel_directory = directory.join(SENTINEL_DIRECTORY);
+        let reopened = lock_process(&sentinel_directory).unwrap_or_else(|err| {
+            warn!(
+                "Failed to lock {}, other processes may take this one's sentinels for a crash: \
+                 {:?}",
+                sentinel_directory.display(),
+                err
+            );
+            false
+        });
+        let sentinels: Vec<PathBuf> = sentinel_paths(&sentinel_directory)
+            .into_iter()
+            .filter(|path| !is_owner_running(&sentinel_directory, path, reopened))
+            .collect();
+        remove_stale_locks(&sentinel_directory);
+        if config.safe_mode.enabled && !sentinels.is_empty() {
+            let mut initializing = Vec::new();
+            let mut unreadable = false;
+            for path in &sentinels {
+                match std::fs::read_to_string(path) {
+                    Ok(identifier) if identifier.parse::<ExtensionIdentifier>().is_ok() => {
+                        initializing.push(identifier)
+                    }
+                    _ => unreadable = true,
+                }
+            }
+            warn!(
+                "The previous run crashed while initializing [{}], starting in safe mode",
+                initializing.join(", ")
+            );
+            for suspect in initializing {
+                if !skipped.suspects.contains(&suspect) {
+                    skipped.suspects.push(suspect);
+                }
+            }
+            // An unreadable sentinel doesn't say who crashed, so nothing can be trusted.
+            skipped.all |= config.safe_mode.skip_all || unreadable;
+            std::fs::create_dir_all(&directory)?;
+            std::fs::write(directory.join(SKIPPED_FILE), serde_json::to_vec(&skipped)?)?;
+        }
+        for path in sentinels {
+            if let Err(err) = std::fs::remove_file(&path) {
+                warn!(
+                    "Failed to remove the crash sentinel {}: {}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+        Ok(Self {
+            enabled: config.safe_mode.enabled,
+            directory,
+            skipped: Mutex::new(skipped),
+        })
+    }
+
+    /// Rejects loading an extension skipped after a crash, unless safe mode is disabled in the
+    /// host config.
+    pub fn check(&self, identifier: &ExtensionIdentifier) -> anyhow::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let skipped = self.skipped.lock().unwrap();
+        let key = identifier.unversioned();
+        if skipped.all
+            || skipped
+                .suspects
+                .iter()
+                .any(|suspect| is_same(suspect, &key))
+        {
+            return Err(anyhow!(
+                "Skipped {} in safe mode after a crash during initialization; re-enable it to \
+                 load it again",
+                identifier
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn skipped(&self) -> SkippedExtensions {
+        self.skipped.lock().unwrap().clone()
+    }
+
+    /// Lets a skipped extension load again, or every skipped extension if `id` is `None`.
+    pub fn reenable(&self, id: Option<&str>) -> anyhow::Result<()> {
+        let mut skipped = self.skipped.lock().unwrap();
+        match id {
+            None => *skipped = SkippedExtensions::default(),
+            Some(_) if skipped.all => {
+                return Err(anyhow!(
+                    "All extensions are skipped in safe mode, re-enable them all at once"
+                ));
+            }
+            Some(id) => {
+                let key = id.parse::<ExtensionIdentifier>()?.unversioned();
+                let before = skipped.suspects.len();
+                skipped.suspects.retain(|suspect| !is_same(suspect, &key));
+                if skipped.suspects.len() == before {
+                    return Err(anyhow!("Extension {} is not skipped", id));
+                }
+            }
+        }
+        let path = self.directory.join(SKIPPED_FILE);
+        if skipped.all || !skipped.suspects.is_empty() {
+            std::fs::write(path, serde_json::to_vec(&*skipped)?)?;
+        } else if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a sentinel naming `identifier` as initializing; it is removed when the returned
+    /// guard is dropped, so it only survives a crash of the process. Each initializer gets
+    /// its own sentinel, so initializers running at the same time don't erase each other's.
+    pub fn initializing(&self, identifier: &ExtensionIdentifier) -> InitSentinel {
+        if !self.enabled {
+            return InitSentinel { path: None };
+        }
+        let directory = self.directory.join(SENTINEL_DIRECTORY);
+        let counter = SENTINEL_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = directory.join(format!(
+            "{}-{}{}",
+            std::process::id(),
+            counter,
+            SENTINEL_SUFFIX
+        ));
+        let written = std::fs::create_dir_all(&directory)
+            .and_then(|_| std::fs::write(&path, identifier.to_string()));
+        if let Err(err) = written {
+            warn!(
+                "Failed to write the crash sentinel {}: {:?}",
+                path.display(),
+                err
+            );
+            return InitSentinel { path: None };
+        }
+        InitSentinel { path: Some(path) }
+    }
+}
+
+/// Removes the crash sentinel when dropped, see [`SafeMode::initializing`].
+pub struct InitSentinel {
+    path: Option<PathBuf>,
+}
+
+impl Drop for InitSentinel {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path
+            && let Err(err) = std::fs::remove_file(path)
+        {
+            warn!(
+                "Failed to remove the crash sentinel {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Sentinels left in `directory`, none if it doesn't exist.
+fn sentinel_paths(directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.to_string_lossy().ends_with(SENTINEL_SUFFIX))
+        .collect()
+}
+
+/// Takes this process's lock in `directory`, returning whether an earlier [`SafeMode::open`]
+/// of the process already holds it.
+fn lock_process(directory: &Path) -> anyhow::Result<bool> {
+    let mut locks = PROCESS_LOCKS.lock().unwrap();
+    if locks.iter().any(|(locked, _)| locked == directory) {
+        return Ok(true);
+    }
+    std::fs::create_dir_all(directory)?;
+    let file = File::create(lock_path(directory, std::process::id()))?;
+    file.try_lock()?;
+    locks.push((directory.to_path_buf(), file));
+    Ok(false)
+}
+
+fn lock_path(directory: &Path, pid: u32) -> PathBuf {
+    directory.join(format!("{}{}", pid, LOCK_SUFFIX))
+}
+
+/// Whether the process that wrote the sentinel at `path` still holds its lock. Sentinels
+/// carrying this process's id were written by an earlier process that had the same id,
+/// unless this process already opened the directory before.
+fn is_owner_running(directory: &Path, path: &Path, reopened: bool) -> bool {
+    let pid = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split_once('-'))
+        .and_then(|(pid, _)| pid.parse::<u32>().ok());
+    match pid {
+        Some(pid) if pid == std::process::id() => reopened,
+        Some(pid) => File::open(lock_path(directory, pid))
+            .is_ok_and(|file| matches!(file.try_lock(), Err(TryLockError::WouldBlock))),
+        None => false,
+    }
+}
+
+/// Deletes the lock files of processes that are no longer running.
+fn remove_stale_locks(directory: &Path) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    let own = lock_path(directory, std::process::id());
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        if path == own || !path.to_string_lossy().ends_with(LOCK_SUFFIX) {
+            continue;
+        }
+        let stale = File::open(&path).is_ok_and(|file| file.try_lock().is_ok());
+        if stale {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Whether the recorded identifier `suspect` names the extension `key`, whatever its version.
+fn is_same(suspect: &str, key: &ExtensionIdentifier) -> bool {
+    suspect
+        .parse::<ExtensionIdentifier>()
+        .is_ok_and(|identifier| &identifier.unversioned() == key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Host config with its own cache directory under the system temp directory.
+    fn config(name: &str) -> HostConfig {
+        let mut config = HostConfig::default();
+        config.cache.directory =
+            std::env::temp_dir().join(format!("ark-safe-mode-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&config.cache.directory);
+        config
+    }
+
+    fn identifier(s: &str) -> ExtensionIdentifier {
+        s.parse().unwrap()
+    }
+
+    /// Process id no test process has, so no lock file of it exists.
+    static CRASHED_PID: u32 = u32::MAX;
+
+    /// Leaves a sentinel of `identifier` behind, as a process that crashed during its
+    /// initializer would.
+    fn crash_initializing(config: &HostConfig, identifier: &str) {
+        let directory = config.cache.directory.join(SENTINEL_DIRECTORY);
+        std::fs::create_dir_all(&directory).unwrap();
+        let counter = SENTINEL_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let name = format!("{}-{}{}", CRASHED_PID, counter, SENTINEL_SUFFIX);
+        std::fs::write(directory.join(name), identifier).unwrap();
+    }
+
+    #[test]
+    fn skips_extensions_that_crashed_initializing() {
+        let config = config("crash");
+        let safe_mode = SafeMode::open(&config).unwrap();
+        drop(safe_mode.initializing(&identifier("acme:fine@1.0.0")));
+        crash_initializing(&config, "acme:one@1.0.0");
+        crash_initializing(&config, "acme:two@1.0.0");
+
+        let safe_mode = SafeMode::open(&config).unwrap();
+        assert!(safe_mode.check(&identifier("acme:one@2.0.0")).is_err());
+        assert!(safe_mode.check(&identifier("acme:two@1.0.0")).is_err());
+        assert!(safe_mode.check(&identifier("acme:fine@1.0.0")).is_ok());
+        assert!(!safe_mode.skipped().all);
+
+        let safe_mode = SafeMode::open(&config).unwrap();
+        assert_eq!(safe_mode.skipped().suspects.len(), 2);
+        safe_mode.reenable(Some("acme:one")).unwrap();
+        assert!(safe_mode.reenable(Some("acme:one")).is_err());
+        assert!(safe_mode.check(&identifier("acme:one@2.0.0")).is_ok());
+        assert!(safe_mode.check(&identifier("acme:two@1.0.0")).is_err());
+
+        let safe_mode = SafeMode::open(&config).unwrap();
+        assert_eq!(safe_mode.skipped().suspects, ["acme:two@1.0.0"]);
+        safe_mode.reenable(None).unwrap();
+        assert!(!config.cache.directory.join(SKIPPED_FILE).exists());
+        std::fs::remove_dir_all(&config.cache.directory).unwrap();
+    }
+
+    #[test]
+    fn skips_everything_after_an_unreadable_sentinel() {
+        let config = config("unreadable");
+        let directory = config.cache.directory.join(SENTINEL_DIRECTORY);
+        std::fs::create_dir_all(&directory).unwrap();
+        let name = format!("{}-0{}", CRASHED_PID, SENTINEL_SUFFIX);
+        std::fs::write(directory.join(name), "").unwrap();
+
+        let safe_mode = SafeMode::open(&config).unwrap();
+        assert!(safe_mode.skipped().all);
+        assert!(safe_mode.check(&identifier("acme:one")).is_err());
+        assert!(safe_mode.reenable(Some("acme:one")).is_err());
+        assert!(sentinel_paths(&directory).is_empty());
+        safe_mode.reenable(None).unwrap();
+        assert!(safe_mode.check(&identifier("acme:one")).is_ok());
+        std::fs::remove_dir_all(&config.cache.directory).unwrap();
+    }
+
+    #[test]
+    fn does_nothing_when_disabled() {
+        let mut config = config("disabled");
+        config.safe_mode.enabled = false;
+        let safe_mode = SafeMode::open(&config).unwrap();
+        std::mem::forget(safe_mode.initializing(&identifier("acme:one@1.0.0")));
+        assert!(sentinel_paths(&config.cache.directory.join(SENTINEL_DIRECTORY)).is_empty());
+
+        config.safe_mode.enabled = true;
+        crash_initializing(&config, "acme:one@1.0.0");
+        config.safe_mode.enabled = false;
+        let safe_mode = SafeMode::open(&config).unwrap();
+        assert!(safe_mode.check(&identifier("acme:one@1.0.0")).is_ok());
+        config.safe_mode.enabled = true;
+        assert!(
+            SafeMode::open(&config)
+                .unwrap()
+                .skipped()
+                .suspects
+                .is_empty()
+        );
+        std::fs::remove_dir_all(&config.cache.directory).unwrap();
+    }
+
+    #[test]
+    fn keeps_sentinels_of_running_processes() {
+        let config = config("running");
+        let directory = config.cache.directory.join(SENTINEL_DIRECTORY);
+        let safe_mode = SafeMode::open(&config).unwrap();
+        std::mem::forget(safe_mode.initializing(&identifier("acme:one@1.0.0")));
+        let other_pid = u32::MAX - 1;
+        let other_lock = File::create(lock_path(&directory, other_pid)).unwrap();
+        other_lock.try_lock().unwrap();
+        let name = format!("{}-0{}", other_pid, SENTINEL_SUFFIX);
+        std::fs::write(directory.join(name), "acme:two@1.0.0").unwrap();
+
+        let safe_mode = SafeMode::open(&config).unwrap();
+        assert!(safe_mode.check(&identifier("acme:one@1.0.0")).is_ok());
+        assert!(safe_mode.check(&identifier("acme:two@1.0.0")).is_ok());
+        assert_eq!(sentinel_paths(&directory).len(), 2);
+
+        drop(other_lock);
+        let safe_mode = SafeMode::open(&config).unwrap();
+        assert!(safe_mode.check(&identifier("acme:one@1.0.0")).is_ok());
+        assert!(safe_mode.check(&identifier("acme:two@1.0.0")).is_err());
+        assert_eq!(sentinel_paths(&directory).len(), 1);
+        assert!(!lock_path(&directory, other_pid).exists());
+        std::fs::remove_dir_all(&config.cache.directory).unwrap();
+    }
+}
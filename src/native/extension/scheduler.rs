@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::extension::{package::ExtensionIdentifier, wasm::Registration};
+
+/// Handle of a scheduled task, unique for the lifetime of the runtime.
+pub type TaskHandle = u64;
+
+#[derive(Debug, Clone, Copy)]
+enum Schedule {
+    /// Runs once at the first tick at or after `due`.
+    Once { due: u64 },
+    /// Runs at the first tick at or after `due`, then every `interval` milliseconds.
+    Every { due: u64, interval: u64 },
+    /// Runs on every tick.
+    Tick,
+}
+
+struct ScheduledTask {
+    registration: Registration,
+    schedule: Schedule,
+}
+
+/// Timers and per-tick callbacks of extensions, driven by the host through [`Scheduler::due`].
+/// Time is whatever millisecond clock the host passes to its ticks; delays are measured from
+/// the most recent tick.
+#[derive(Default)]
+pub struct Scheduler {
+    next_handle: TaskHandle,
+    now: u64,
+    tasks: HashMap<TaskHandle, ScheduledTask>,
+}
+
+impl Scheduler {
+    pub fn after(&mut self, delay: u64, registration: Registration) -> TaskHandle {
+        let due = self.now.saturating_add(delay);
+        self.insert(registration, Schedule::Once { due })
+    }
+
+    pub fn every(&mut self, interval: u64, registration: Registration) -> TaskHandle {
+        let interval = interval.max(1);
+        let due = self.now.saturating_add(interval);
+        self.insert(registration, Schedule::Every { due, interval })
+    }
+
+    pub fn on_tick(&mut self, registration: Registration) -> TaskHandle {
+        self.insert(registration, Schedule::Tick)
+    }
+
+    fn insert(&mut self, registration: Registration, schedule: Schedule) -> TaskHandle {
+        self.next_handle += 1;
+        self.tasks.insert(
+            self.next_handle,
+            ScheduledTask {
+                registration,
+                schedule,
+            },
+        );
+        self.next_handle
+    }
+
+    /// Cancels a task of `owner`, returning `false` if it doesn't exist or belongs to another
+    /// extension.
+    pub fn cancel(&mut self, owner: &ExtensionIdentifier, handle: TaskHandle) -> bool {
+        match self.tasks.get(&handle) {
+            Some(task) if &task.registration.owner == owner => {
+                self.tasks.remove(&handle);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Cancels every task of `owner`.
+    pub fn cancel_owned(&mut self, owner: &ExtensionIdentifier) {
+        self.tasks
+            .retain(|_, task| &task.registration.owner != owner);
+    }
+
+    /// Advances the clock to `now` and returns the tasks to run, earliest first. Repeating
+    /// tasks are rescheduled, skipping intervals missed entirely instead of running once per
+    /// missed interval. Each task is then started with [`Scheduler::start`], so a task
+    /// cancelled by an earlier callback of the same tick doesn't run.
+    pub fn due(&mut self, now: u64) -> Vec<TaskHandle> {
+        self.now = self.now.max(now);
+        let now = self.now;
+        let mut due: Vec<(u64, TaskHandle)> = Vec::new();
+        for (handle, task) in &mut self.tasks {
+            match &mut task.schedule {
+                Schedule::Once { due: at } if *at <= now => due.push((*at, *handle)),
+                Schedule::Every { due: at, interval } if *at <= now => {
+                    due.push((*at, *handle));
+                    *at = at.saturating_add(*interval);
+                    if *at <= now {
+                        *at = now + *interval;
+                    }
+                }
+                Schedule::Tick => due.push((now, *handle)),
+                _ => {}
+            }
+        }
+        due.sort();
+        due.into_iter().map(|(_, handle)| handle).collect()
+    }
+
+    /// Returns the handler of a due task, removing the task if it only runs once, or `None`
+    /// if it was cancelled meanwhile.
+    pub fn start(&mut self, handle: TaskHandle) -> Option<Registration> {
+        let task = self.tasks.get(&handle)?;
+        let registration = task.registration.clone();
+        if let Schedule::Once { .. } = task.schedule {
+            self.tasks.remove(&handle);
+        }
+        Some(registration)
+    }
+
+    /// Removes a task whose callback failed, so a broken repeating task doesn't fail every
+    /// tick.
+    pub fn remove(&mut self, handle: TaskHandle) {
+        self.tasks.remove(&handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extension::{testing, wasm::RegisteredFunc};
+
+    fn registration(owner: &str) -> Registration {
+        let (_, run) = testing::core_func(r#"(module (func (export "run")))"#);
+        Registration {
+            func: RegisteredFunc::Module(run),
+            owner: owner.parse().unwrap(),
+            store_id: 1,
+            affinity: Default::default(),
+        }
+    }
+
+    #[test]
+    fn runs_tasks_when_due_earliest_first() {
+        let mut scheduler = Scheduler::default();
+        let late = scheduler.after(20, registration("acme:one"));
+        let early = scheduler.after(10, registration("acme:one"));
+        let tick = scheduler.on_tick(registration("acme:two"));
+        assert_eq!(scheduler.due(5), [tick]);
+        assert_eq!(scheduler.due(25), [early, late, tick]);
+        assert!(scheduler.start(early).is_some());
+        assert!(scheduler.start(late).is_some());
+        assert!(scheduler.start(tick).is_some());
+        assert_eq!(scheduler.due(30), [tick]);
+        assert!(scheduler.start(early).is_none());
+    }
+
+    #[test]
+    fn measures_delays_from_the_latest_tick() {
+        let mut scheduler = Scheduler::default();
+        scheduler.due(100);
+        let task = scheduler.after(10, registration("acme:one"));
+        assert!(scheduler.due(50).is_empty());
+        assert!(scheduler.due(109).is_empty());
+        assert_eq!(scheduler.due(110), [task]);
+    }
+
+    #[test]
+    fn skips_missed_intervals() {
+        let mut scheduler = Scheduler::default();
+        let task = scheduler.every(10, registration("acme:one"));
+        assert!(scheduler.due(9).is_empty());
+        assert_eq!(scheduler.due(10), [task]);
+        assert_eq!(scheduler.due(55), [task]);
+        assert!(scheduler.due(64).is_empty());
+        assert_eq!(scheduler.due(65), [task]);
+        assert!(scheduler.start(task).is_some());
+        assert!(scheduler.start(task).is_some());
+    }
+
+    #[test]
+    fn cancelled_tasks_do_not_start() {
+        let mut scheduler = Scheduler::default();
+        let one = scheduler.on_tick(registration("acme:one"));
+        let two = scheduler.on_tick(registration("acme:two"));
+        let three = scheduler.on_tick(registration("acme:two"));
+        assert_eq!(scheduler.due(1), [one, two, three]);
+        assert!(!scheduler.cancel(&"acme:one".parse().unwrap(), two));
+        assert!(scheduler.cancel(&"acme:two".parse().unwrap(), two));
+        scheduler.cancel_owned(&"acme:one".parse().unwrap());
+        assert!(scheduler.start(one).is_none());
+        assert!(scheduler.start(two).is_none());
+        assert!(scheduler.start(three).is_some());
+        scheduler.remove(three);
+        assert!(scheduler.due(2).is_empty());
+    }
+}
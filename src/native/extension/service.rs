@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use wasmtime::{
+    Engine,
+    component::{
+        Component, Linker, Val,
+        types::{self, ComponentItem, Type},
+    },
+};
+
+use crate::extension::{
+    compat,
+    loaded::LoadedExtension,
+    metrics,
+    package::{ExtensionIdentifier, ExtensionManifest},
+    wasm::{ExtensionContext, ExtensionInstance, LoadedExtensions, call_guest},
+};
+
+/// An import of an extension served by an interface another extension publishes through
+/// its manifest's `provides` list.
+#[derive(Debug, Clone)]
+pub struct ServiceImport {
+    /// Import name as the importing component spells it, e.g. `acme:waypoints/api@1.0.0`.
+    pub name: String,
+    /// Unversioned identifier of the providing extension.
+    pub provider: ExtensionIdentifier,
+    /// Export name of the interface in the providing component.
+    pub export: String,
+}
+
+/// Matches the component's imports that the host doesn't provide against the interfaces
+/// published by loaded extensions the manifest depends on. Imports nothing provides are
+/// left for the compatibility check to report.
+pub fn resolve_imports(
+    engine: &Engine,
+    component: &Component,
+    manifest: &ExtensionManifest,
+    loaded: &HashMap<ExtensionIdentifier, Arc<LoadedExtension>>,
+) -> anyhow::Result<Vec<ServiceImport>> {
+    let requirements = manifest.requirements()?;
+    let providers: Vec<&Arc<LoadedExtension>> = loaded
+        .values()
+        .filter(|extension| {
+            requirements
+                .iter()
+                .any(|requirement| requirement.matches(&extension.identifier))
+        })
+        .collect();
+    let mut imports = Vec::new();
+    for (name, item) in component.component_type().imports(engine) {
+        if !matches!(item, ComponentItem::ComponentInstance(_))
+            || name.starts_with("ark:core/")
+            || name.starts_with("wasi:")
+        {
+            continue;
+        }
+        let (base, version) = split_version(name);
+        let provided = providers.iter().find_map(|extension| {
+            extension
+                .package
+                .manifest
+                .provides
+                .iter()
+                .find(|provided| {
+                    let (provided_base, provided_version) = split_version(provided);
+                    provided_base == base
+                        && match (version, provided_version) {
+                            (Some(expected), Some(provided)) => {
+                                compat::is_semver_compatible(expected, provided)
+                            }
+                            (None, None) => true,
+                            _ => false,
+                        }
+                })
+                .map(|provided| (extension, provided))
+        });
+        if let Some((extension, export)) = provided {
+            imports.push(ServiceImport {
+                name: name.to_string(),
+                provider: extension.identifier.unversioned(),
+                export: export.clone(),
+            });
+        }
+    }
+    Ok(imports)
+}
+
+/// Defines every function of the resolved service imports in `linker` as a host function
+/// that calls the provider's export in the provider's own store. Values are copied across
+/// the two stores, so interfaces passing resources, futures or streams can't be imported.
+pub fn define_imports(
+    engine: &Engine,
+    linker: &mut Linker<ExtensionContext>,
+    component: &Component,
+    imports: &[ServiceImport],
+    loaded: &LoadedExtensions,
+) -> anyhow::Result<()> {
+    let ty = component.component_type();
+    for import in imports {
+        let Some(ComponentItem::ComponentInstance(interface)) = ty
+            .imports(engine)
+            .find(|(name, _)| *name == import.name)
+            .map(|(_, item)| item)
+        else {
+            continue;
+        };
+        let mut instance = linker.instance(&import.name)?;
+        for (function, item) in interface.exports(engine) {
+            let func = match item {
+                ComponentItem::ComponentFunc(func) => func,
+                ComponentItem::Type(_) | ComponentItem::Resource(_) => continue,
+                _ => {
+                    return Err(anyhow!(
+                        "Service {} exports `{}`, which is not a function",
+                        import.name,
+                        function
+                    ));
+                }
+            };
+            if !is_transferable_func(&func) {
+                return Err(anyhow!(
+                    "Service function `{}` of {} passes resources, futures or streams, which \
+                     can't cross extensions",
+                    function,
+                    import.name
+                ));
+            }
+            let loaded = loaded.clone();
+            let provider = import.provider.clone();
+            let export = import.export.clone();
+            let name = function.to_string();
+            instance.func_new(function, move |_, _, params, results| {
+                call_service(&loaded, &provider, &export, &name, params, results)
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Calls a function of a service export in the provider's store, locking the provider like
+/// any other guest call so that cycles between extensions fail instead of deadlocking.
+fn call_service(
+    loaded: &LoadedExtensions,
+    provider: &ExtensionIdentifier,
+    export: &str,
+    function: &str,
+    params: &[Val],
+    results: &mut [Val],
+) -> wasmtime::Result<()> {
+    let extension = loaded
+        .lock()
+        .unwrap()
+        .get(provider)
+        .cloned()
+        .ok_or(wasmtime::Error::msg(format!(
+            "Service provider {} is not loaded",
+            provider
+        )))?;
+    let mut guard = extension.lock()?;
+    let (store, instance) = &mut *guard;
+    let ExtensionInstance::Component(instance) = *instance else {
+        return Err(wasmtime::Error::msg(format!(
+            "Service provider {} is not a component",
+            provider
+        )));
+    };
+    let func = instance
+        .get_export_index(&mut *store, None, export)
+        .and_then(|interface| instance.get_export_index(&mut *store, Some(&interface), function))
+        .and_then(|index| instance.get_func(&mut *store, index))
+        .ok_or(wasmtime::Error::msg(format!(
+            "{} does not export `{}` in {}",
+            provider, function, export
+        )))?;
+    let trigger = format!("{}{}", metrics::SERVICE_TRIGGER_PREFIX, export);
+    call_guest(store, &trigger, |store| {
+        Ok(func.call(&mut *store, params, results)?)
+    })
+    .map_err(wasmtime::Error::from_anyhow)
+}
+
+/// Checks that the component exports every interface its manifest says it provides.
+pub fn check_provided(
+    engine: &Engine,
+    ty: &types::Component,
+    manifest: &ExtensionManifest,
+) -> Vec<compat::CompatibilityIssue> {
+    manifest
+        .provides
+        .iter()
+        .filter(|name| {
+            !matches!(
+                ty.get_export(engine, name),
+                Some(ComponentItem::ComponentInstance(_))
+            )
+        })
+        .map(|name| compat::CompatibilityIssue::InvalidExport {
+            name: name.clone(),
+            detail: "listed in `provides` but not exported as an interface".to_string(),
+        })
+        .collect()
+}
+
+fn split_version(name: &str) -> (&str, Option<&str>) {
+    match name.split_once('@') {
+        Some((base, version)) => (base, Some(version)),
+        None => (name, None),
+    }
+}
+
+fn is_transferable_func(func: &types::ComponentFunc) -> bool {
+    func.params().all(|(_, ty)| is_transferable(&ty))
+        && func.results().all(|ty| is_transferable(&ty))
+}
+
+/// Whether values of `ty` can be copied from one store to another.
+fn is_transferable(ty: &Type) -> bool {
+    match ty {
+        Type::List(list) => is_transferable(&list.ty()),
+        Type::Record(record) => record.fields().all(|field| is_transferable(&field.ty)),
+        Type::Tuple(tuple) => tuple.types().all(|ty| is_transferable(&ty)),
+        Type::Variant(variant) => variant
+            .cases()
+            .all(|case| case.ty.as_ref().is_none_or(is_transferable)),
+        Type::Option(option) => is_transferable(&option.ty()),
+        Type::Result(result) => {
+            result.ok().as_ref().is_none_or(is_transferable)
+                && result.err().as_ref().is_none_or(is_transferable)
+        }
+        Type::Own(_) | Type::Borrow(_) | Type::Future(_) | Type::Stream(_) | Type::ErrorContext => {
+            false
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::extension::testing;
+
+    const PROVIDER: &str = r#"
+        (component
+          (core module $m
+            (func (export "add") (param i32 i32) (result i32)
+              local.get 0
+              local.get 1
+              i32.add))
+          (core instance $i (instantiate $m))
+          (func $add (param "a" u32) (param "b" u32) (result u32)
+            (canon lift (core func $i "add")))
+          (instance $api (export "add" (func $add)))
+          (export "acme:math/api@1.2.0" (instance $api)))
+    "#;
+
+    const CONSUMER: &str = r#"
+        (component
+          (import "acme:math/api@1.0.0" (instance $api
+            (export "add" (func (param "a" u32) (param "b" u32) (result u32)))))
+          (core func $add (canon lower (func $api "add")))
+          (core module $m
+            (import "api" "add" (func $add (param i32 i32) (result i32)))
+            (func (export "run") (result i32)
+              i32.const 2
+              i32.const 3
+              call $add))
+          (core instance $i (instantiate $m
+            (with "api" (instance (export "add" (func $add))))))
+          (func (export "run") (result u32) (canon lift (core func $i "run"))))
+    "#;
+
+    fn provider_manifest() -> ExtensionManifest {
+        testing::manifest(json!({
+            "id": "math",
+            "namespaces": "acme",
+            "version": "1.2.0",
+            "provides": ["acme:math/api@1.2.0"],
+        }))
+    }
+
+    fn consumer_manifest(dependencies: &[&str]) -> ExtensionManifest {
+        testing::manifest(json!({
+            "id": "calc",
+            "namespaces": "acme",
+            "dependencies": dependencies,
+        }))
+    }
+
+    /// Loads the provider component into a map of loaded extensions.
+    fn load_provider(engine: &Engine) -> LoadedExtensions {
+        let component = Component::new(engine, PROVIDER).unwrap();
+        let mut store = testing::store(engine, provider_manifest(), &component, Vec::new());
+        let linker = Linker::new(engine);
+        let instance = linker.instantiate(&mut store, &component).unwrap();
+        let extension = LoadedExtension::new(store, ExtensionInstance::Component(instance), None);
+        let loaded = HashMap::from([(extension.identifier.unversioned(), Arc::new(extension))]);
+        Arc::new(Mutex::new(loaded))
+    }
+
+    #[test]
+    fn resolves_imports_provided_by_dependencies() {
+        let engine = Engine::default();
+        let loaded = load_provider(&engine);
+        let consumer = Component::new(&engine, CONSUMER).unwrap();
+        let manifest = consumer_manifest(&["acme:math@^1.0"]);
+        let imports =
+            resolve_imports(&engine, &consumer, &manifest, &loaded.lock().unwrap()).unwrap();
+        assert_eq!(imports.len(), 1);
+        assert_eq!(imports[0].name, "acme:math/api@1.0.0");
+        assert_eq!(imports[0].provider.to_string(), "acme:math");
+        assert_eq!(imports[0].export, "acme:math/api@1.2.0");
+    }
+
+    #[test]
+    fn leaves_imports_of_non_dependencies_unresolved() {
+        let engine = Engine::default();
+        let loaded = load_provider(&engine);
+        let consumer = Component::new(&engine, CONSUMER).unwrap();
+        let loaded = loaded.lock().unwrap();
+        for dependencies in [&[][..], &["acme:math@^2.0"], &["acme:other"]] {
+            let manifest = consumer_manifest(dependencies);
+            let imports = resolve_imports(&engine, &consumer, &manifest, &loaded).unwrap();
+            assert!(imports.is_empty(), "{:?}", dependencies);
+        }
+    }
+
+    #[test]
+    fn forwards_calls_to_the_provider_store() {
+        let engine = Engine::default();
+        let loaded = load_provider(&engine);
+        let consumer = Component::new(&engine, CONSUMER).unwrap();
+        let manifest = consumer_manifest(&["acme:math"]);
+        let imports =
+            resolve_imports(&engine, &consumer, &manifest, &loaded.lock().unwrap()).unwrap();
+        let mut linker = Linker::new(&engine);
+        define_imports(&engine, &mut linker, &consumer, &imports, &loaded).unwrap();
+        let mut store = testing::store(&engine, manifest, &consumer, imports);
+        let instance = linker.instantiate(&mut store, &consumer).unwrap();
+        let run = instance
+            .get_typed_func::<(), (u32,)>(&mut store, "run")
+            .unwrap();
+        assert_eq!(run.call(&mut store, ()).unwrap(), (5,));
+    }
+
+    #[test]
+    fn fails_calls_once_the_provider_is_unloaded() {
+        let engine = Engine::default();
+        let loaded = load_provider(&engine);
+        let consumer = Component::new(&engine, CONSUMER).unwrap();
+        let manifest = consumer_manifest(&["acme:math"]);
+        let imports =
+            resolve_imports(&engine, &consumer, &manifest, &loaded.lock().unwrap()).unwrap();
+        let mut linker = Linker::new(&engine);
+        define_imports(&engine, &mut linker, &consumer, &imports, &loaded).unwrap();
+        let mut store = testing::store(&engine, manifest, &consumer, imports);
+        let instance = linker.instantiate(&mut store, &consumer).unwrap();
+        loaded.lock().unwrap().clear();
+        let run = instance
+            .get_typed_func::<(), (u32,)>(&mut store, "run")
+            .unwrap();
+        let err = run.call(&mut store, ()).unwrap_err();
+        assert!(format!("{:?}", err).contains("Service provider acme:math is not loaded"));
+    }
+
+    #[test]
+    fn rejects_functions_that_cannot_cross_stores() {
+        // The host engine doesn't parse futures or streams yet; reject them all the same.
+        let mut config = wasmtime::Config::new();
+        config.wasm_component_model_async(true);
+        let engine = Engine::new(&config).unwrap();
+        let loaded: LoadedExtensions = Arc::new(Mutex::new(HashMap::new()));
+        for ty in ["(own $r)", "(future u32)", "(stream u32)"] {
+            let wat = format!(
+                r#"(component
+                  (import "acme:math/api@1.0.0" (instance
+                    (export "r" (type $r (sub resource)))
+                    (export "take" (func (param "value" {})))))
+                )"#,
+                ty
+            );
+            let component = Component::new(&engine, wat).unwrap();
+            let imports = [ServiceImport {
+                name: "acme:math/api@1.0.0".to_string(),
+                provider: "acme:math".parse().unwrap(),
+                export: "acme:math/api@1.0.0".to_string(),
+            }];
+            let mut linker = Linker::new(&engine);
+            let err =
+                define_imports(&engine, &mut linker, &component, &imports, &loaded).unwrap_err();
+            assert!(err.to_string().contains("`take`"), "{}: {}", ty, err);
+        }
+    }
+
+    #[test]
+    fn checks_provided_interfaces_are_exported() {
+        let engine = Engine::default();
+        let component = Component::new(&engine, PROVIDER).unwrap();
+        let ty = component.component_type();
+        assert!(check_provided(&engine, &ty, &provider_manifest()).is_empty());
+        let manifest = testing::manifest(json!({
+            "id": "math",
+            "provides": ["acme:math/api@1.2.0", "acme:math/extra@1.0.0"],
+        }));
+        let issues = check_provided(&engine, &ty, &manifest);
+        assert!(matches!(
+            &issues[..],
+            [compat::CompatibilityIssue::InvalidExport { name, .. }]
+                if name == "acme:math/extra@1.0.0"
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use wasmtime::{
+    Engine, Instance, Module, Store, StoreLimitsBuilder, TypedFunc,
+    component::{self, Component, ComponentNamedList, Lift, Linker, Lower},
+};
+use wasmtime_wasi::{ResourceTable, WasiCtx};
+
+use crate::extension::{
+    binding::WorldVersion,
+    bus::Bus,
+    crash::RecentLogs,
+    i18n::Translations,
+    metrics::{MeteredLimits, SharedMetrics},
+    package::{ExtensionIdentifier, ExtensionManifest, ExtensionPackage, PackageHash},
+    scheduler::Scheduler,
+    service::ServiceImport,
+    wasm::{ExtensionBinary, ExtensionContext, LaunchArgs},
+};
+
+/// Instantiates the core module `wat` in a store of its own, returning the store and the
+/// module's `run` export.
+pub fn core_func(wat: &str) -> (Store<()>, TypedFunc<(), ()>) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wat).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let run = instance.get_typed_func(&mut store, "run").unwrap();
+    (store, run)
+}
+
+/// Calls a guest function that executes `unreachable`, converting its error into `anyhow`
+/// like the runtime's guest calls do.
+pub fn unreachable_error() -> anyhow::Error {
+    let (mut store, run) = core_func(r#"(module (func (export "run") unreachable))"#);
+    run.call(&mut store, ()).unwrap_err().into()
+}
+
+/// Instantiates the component `wat` in a store of its own, returning the store and the
+/// component's `name` export.
+pub fn component_func<Params, Results>(
+    wat: &str,
+    name: &str,
+) -> (Store<()>, component::TypedFunc<Params, Results>)
+where
+    Params: ComponentNamedList + Lower,
+    Results: ComponentNamedList + Lift,
+{
+    let engine = Engine::default();
+    let component = Component::new(&engine, wat).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Linker::new(&engine)
+        .instantiate(&mut store, &component)
+        .unwrap();
+    let func = instance.get_typed_func(&mut store, name).unwrap();
+    (store, func)
+}
+
+/// Parses a manifest from JSON, filling in the defaults of missing fields.
+pub fn manifest(json: serde_json::Value) -> ExtensionManifest {
+    serde_json::from_value(json).unwrap()
+}
+
+/// Creates a store for `component` as it would be loaded from a package without files.
+pub fn store(
+    engine: &Engine,
+    manifest: ExtensionManifest,
+    component: &Component,
+    services: Vec<ServiceImport>,
+) -> Store<ExtensionContext> {
+    let package = ExtensionPackage {
+        manifest,
+        translations: Translations::default(),
+        files: HashMap::new(),
+        hash: PackageHash::of(&[]),
+        verified: false,
+    };
+    let metrics = SharedMetrics::default();
+    Store::new(
+        engine,
+        ExtensionContext {
+            identifier: ExtensionIdentifier::from_manifest(&package.manifest),
+            store_id: 0,
+            package: Arc::new(package),
+            binary: ExtensionBinary::Component(component.clone()),
+            world_version: WorldVersion::LATEST,
+            wasi_ctx: WasiCtx::builder().build(),
+            wasi_p1: None,
+            table: ResourceTable::new(),
+            limits: MeteredLimits::new(StoreLimitsBuilder::new().build(), metrics.clone()),
+            metrics,
+            instance: None,
+            public_registry: Arc::new(Mutex::new(HashMap::new())),
+            scheduler: Arc::new(Mutex::new(Scheduler::default())),
+            bus: Arc::new(Mutex::new(Bus::default())),
+            enabled_vulkan_features: Arc::new(Mutex::new(HashSet::new())),
+            enabled_vulkan_extensions: Arc::new(Mutex::new(HashSet::new())),
+            locale: Arc::new(Mutex::new(String::new())),
+            launch_args: LaunchArgs::default(),
+            background: false,
+            services,
+            profile: None,
+            debug: false,
+            recent_logs: RecentLogs::default(),
+            crash_reporter: None,
+        },
+    )
+}
@@ -1,103 +1,661 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, hash_map::Entry},
     net::{IpAddr, SocketAddr, ToSocketAddrs},
-    path::{self, PathBuf},
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
 };
 
-use anyhow::anyhow;
-use log::error;
+use anyhow::{Context, anyhow};
+use log::{debug, error, warn};
+use rayon::prelude::*;
+use serde::Serialize;
 use wasmtime::{
-    Cache, CacheConfig, Config, Engine, Store,
+    Config, Engine, InstanceAllocationStrategy, Module, Store, StoreLimitsBuilder,
     component::{Component, HasData, Instance, Linker, TypedFunc},
 };
 use wasmtime_wasi::{
     DirPerms, FilePerms, ResourceTable, WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView,
+    p1::WasiP1Ctx,
 };
 
 use crate::{
     extension::{
-        binding,
-        package::{ExtensionPackage, parse_package},
+        binding::{self, WorldVersion},
+        bus::Bus,
+        cache::{self, ArtifactCache},
+        compat,
+        config::HostConfig,
+        crash::{CrashReporter, CrashSummary, RecentLogs},
+        debug,
+        epoch::{self, EpochTicker},
+        i18n::DEFAULT_LOCALE,
+        icon::{Icon, MAX_ICON_DIMENSION},
+        jobs::{self, JobRunner},
+        loaded::{LoadedExtension, StaleHandler},
+        metrics::{self, ExtensionMetrics, MeteredLimits, SharedMetrics},
+        module::{self, BinaryKind, detect_binary_kind},
+        package::{
+            ExtensionIdentifier, ExtensionManifest, ExtensionPackage, HandlerAffinity,
+            parse_package,
+        },
+        policy::ExtensionPolicy,
+        pool,
+        profiling::{self, ActiveProfile},
+        safe_mode::{SafeMode, SkippedExtensions},
+        scheduler::{Scheduler, TaskHandle},
+        service::{self, ServiceImport},
+        watch::{PackageChange, PackageWatcher},
     },
     vulkan::VkBackend,
 };
 
 pub struct WasmRuntime {
-    pub engine: Engine,
+    pub engines: EngineLinkers,
+    /// Engine with on-demand allocation, created when the instance pool of `engines` is
+    /// exhausted.
+    pub on_demand: OnceLock<EngineLinkers>,
+    /// Engine with debug info and without optimizations, created when the first extension in
+    /// debug mode is compiled.
+    pub debug: OnceLock<EngineLinkers>,
     pub loaded_extensions: LoadedExtensions,
-    pub linker: Linker<ExtensionContext>,
     pub registry: Registry,
     pub extension_folder: String,
+    pub policy: ExtensionPolicy,
+    pub config: HostConfig,
+    /// Compiled artifact cache, `None` when disabled by the host config.
+    pub cache: Option<ArtifactCache>,
     pub vulkan: VkBackend,
     pub enabled_vulkan_features: Arc<Mutex<HashSet<String>>>,
     pub enabled_vulkan_extensions: Arc<Mutex<HashSet<String>>>,
+    pub locale: Arc<Mutex<String>>,
+    pub watcher: Mutex<Option<PackageWatcher>>,
+    pub jobs: JobRunner,
+    /// Drives guest profiling and background job timeouts, `None` unless profiling is enabled
+    /// or `jobs.timeout_ms` is set in the host config.
+    pub epoch_ticker: Option<EpochTicker>,
+    pub scheduler: Arc<Mutex<Scheduler>>,
+    pub bus: Arc<Mutex<Bus>>,
+    /// Writes reports of failed guest calls, `None` when disabled by the host config.
+    pub crash_reporter: Option<Arc<CrashReporter>>,
+    pub safe_mode: SafeMode,
+}
+
+/// An engine together with the linkers built for it.
+pub struct EngineLinkers {
+    pub engine: Engine,
+    pub linker: Linker<ExtensionContext>,
+    pub module_linker: wasmtime::Linker<ExtensionContext>,
+}
+
+impl EngineLinkers {
+    fn new(config: &Config) -> anyhow::Result<Self> {
+        let engine = Engine::new(config)?;
+        let mut linker = Linker::<ExtensionContext>::new(&engine);
+        wasmtime_wasi::p2::add_to_linker_sync(&mut linker)?;
+        binding::add_to_linker(&mut linker)?;
+        let mut module_linker = wasmtime::Linker::<ExtensionContext>::new(&engine);
+        module::add_to_linker(&mut module_linker)?;
+        Ok(Self {
+            engine,
+            linker,
+            module_linker,
+        })
+    }
+
+    /// Links and instantiates `binary` in `store`. Service imports recorded in the store are
+    /// defined on a copy of the linker, forwarding to their providers in `loaded`.
+    fn instantiate(
+        &self,
+        store: &mut Store<ExtensionContext>,
+        binary: &ExtensionBinary,
+        loaded: &LoadedExtensions,
+    ) -> anyhow::Result<ExtensionInstance> {
+        Ok(match binary {
+            ExtensionBinary::Component(component) => {
+                let services = &store.data().services;
+                let mut service_linker = None;
+                if !services.is_empty() {
+                    let mut linker = self.linker.clone();
+                    service::define_imports(
+                        &self.engine,
+                        &mut linker,
+                        component,
+                        services,
+                        loaded,
+                    )?;
+                    service_linker = Some(linker);
+                }
+                let instance_pre = compat::link_component(
+                    &self.engine,
+                    service_linker.as_ref().unwrap_or(&self.linker),
+                    component,
+                    &store.data().package.manifest,
+                    store.data().world_version,
+                    services,
+                )?;
+                ExtensionInstance::Component(instance_pre.instantiate(&mut *store)?)
+            }
+            ExtensionBinary::Module(core_module) => ExtensionInstance::Module(module::instantiate(
+                &self.module_linker,
+                store,
+                core_module,
+            )?),
+        })
+    }
 }
 
-type LoadedExtensions = Arc<Mutex<HashMap<String, (Store<ExtensionContext>, Instance)>>>;
+/// Loaded extensions by unversioned identifier. The map lock is only held to look up or
+/// replace entries; guest calls lock the extension itself, see [`LoadedExtension::lock`].
+pub type LoadedExtensions = Arc<Mutex<HashMap<ExtensionIdentifier, Arc<LoadedExtension>>>>;
 
-type Registry = Arc<Mutex<HashMap<String, (TypedFunc<(), ()>, String)>>>;
+type Registry = Arc<Mutex<HashMap<String, Registration>>>;
 
-static CACHE_PATH: &str = "./cache/ark/";
+/// Optional exports of the `stateful` world used to hand state across hot reloads.
+pub static SAVE_STATE_EXPORT: &str = "save-state";
+pub static RESTORE_STATE_EXPORT: &str = "restore-state";
+/// Largest state an extension may hand over; bigger states are dropped with a warning.
+pub static MAX_STATE_SIZE: usize = 16 * 1024 * 1024;
+/// Epoch tick interval used to time out background jobs.
+static JOB_TICK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Source of [`ExtensionContext::store_id`].
+static NEXT_STORE_ID: AtomicU64 = AtomicU64::new(1);
 
 pub struct ExtensionContext {
-    pub package: ExtensionPackage,
-    pub wasm_component: Component,
+    pub identifier: ExtensionIdentifier,
+    /// Unique to this store. Functions looked up in a store can only be called on it, so
+    /// handlers record it to be refused once their instance is replaced.
+    pub store_id: u64,
+    pub package: Arc<ExtensionPackage>,
+    pub binary: ExtensionBinary,
+    pub world_version: WorldVersion,
     pub wasi_ctx: WasiCtx,
+    /// Preview1 context used instead of `wasi_ctx` when the entrypoint is a core module.
+    pub wasi_p1: Option<WasiP1Ctx>,
     pub table: ResourceTable,
-    pub instance: Option<Instance>,
+    pub limits: MeteredLimits,
+    /// Call metrics of the extension, see [`WasmRuntime::extension_metrics`].
+    pub metrics: SharedMetrics,
+    pub instance: Option<ExtensionInstance>,
     pub public_registry: Registry,
+    pub scheduler: Arc<Mutex<Scheduler>>,
+    pub bus: Arc<Mutex<Bus>>,
     pub enabled_vulkan_features: Arc<Mutex<HashSet<String>>>,
     pub enabled_vulkan_extensions: Arc<Mutex<HashSet<String>>>,
+    pub locale: Arc<Mutex<String>>,
+    /// Arguments the extension was launched with, reused when it is upgraded in place.
+    pub launch_args: LaunchArgs,
+    /// Whether a background job is running in the store, which then yields on epoch ticks.
+    pub background: bool,
+    /// Imports resolved to interfaces published by the extensions this one depends on.
+    pub services: Vec<ServiceImport>,
+    /// Guest profile being recorded, see [`WasmRuntime::start_profiling`].
+    pub profile: Option<ActiveProfile>,
+    /// Whether the extension runs in the debug engine, see [`debug::is_enabled`].
+    pub debug: bool,
+    /// Lines the extension logged most recently, included in its crash reports.
+    pub recent_logs: RecentLogs,
+    pub crash_reporter: Option<Arc<CrashReporter>>,
 }
 
 impl HasData for ExtensionContext {
     type Data<'a> = &'a mut ExtensionContext;
 }
 
-impl WasmRuntime {
-    pub fn new(extension_folder: String, vulkan: VkBackend) -> anyhow::Result<Self> {
-        let mut config = Config::new();
+#[derive(Clone)]
+pub enum ExtensionBinary {
+    Component(Component),
+    Module(Module),
+}
 
-        let cache_file = path::absolute(CACHE_PATH)?;
-        let mut cache_config = CacheConfig::new();
-        cache_config.with_directory(cache_file);
-        let cache = Cache::new(cache_config)?;
-        config.cache(Some(cache));
+#[derive(Debug, Clone, Copy)]
+pub enum ExtensionInstance {
+    Component(Instance),
+    Module(wasmtime::Instance),
+}
 
-        let engine = Engine::new(&config)?;
-        let mut linker = Linker::<ExtensionContext>::new(&engine);
-        wasmtime_wasi::p2::add_to_linker_sync(&mut linker)?;
-        binding::add_to_linker(&mut linker)?;
+#[derive(Clone)]
+pub enum RegisteredFunc {
+    Component(TypedFunc<(), ()>),
+    Module(wasmtime::TypedFunc<(), ()>),
+}
+
+/// A handler an extension registered for a trigger.
+#[derive(Clone)]
+pub struct Registration {
+    pub func: RegisteredFunc,
+    pub owner: ExtensionIdentifier,
+    /// Store `func` was looked up in, see [`ExtensionContext::store_id`].
+    pub store_id: u64,
+    pub affinity: HandlerAffinity,
+}
+
+impl RegisteredFunc {
+    pub fn call(&self, store: &mut Store<ExtensionContext>) -> anyhow::Result<()> {
+        match self {
+            RegisteredFunc::Component(fun) => fun.call(&mut *store, ())?,
+            RegisteredFunc::Module(fun) => fun.call(&mut *store, ())?,
+        }
+        Ok(())
+    }
+
+    /// Calls the function on a fiber, suspending at every point where the guest yields.
+    pub async fn call_async(&self, store: &mut Store<ExtensionContext>) -> anyhow::Result<()> {
+        match self {
+            RegisteredFunc::Component(fun) => fun.call_async(&mut *store, ()).await?,
+            RegisteredFunc::Module(fun) => fun.call_async(&mut *store, ()).await?,
+        }
+        Ok(())
+    }
+}
+
+impl ExtensionInstance {
+    /// Calls the exported nullary function `name`, returning `false` if it doesn't exist.
+    pub fn call_export(
+        &self,
+        store: &mut Store<ExtensionContext>,
+        name: &str,
+    ) -> anyhow::Result<bool> {
+        match self {
+            ExtensionInstance::Component(instance) => {
+                let Some(fun) = instance.get_func(&mut *store, name) else {
+                    return Ok(false);
+                };
+                fun.call(&mut *store, &[], &mut [])?;
+            }
+            ExtensionInstance::Module(instance) => {
+                let Some(fun) = instance.get_func(&mut *store, name) else {
+                    return Ok(false);
+                };
+                fun.call(&mut *store, &[], &mut [])?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Calls the `save-state` export, returning `None` if the extension doesn't have one.
+    pub fn save_state(
+        &self,
+        store: &mut Store<ExtensionContext>,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let ExtensionInstance::Component(instance) = self else {
+            return Ok(None);
+        };
+        if instance.get_func(&mut *store, SAVE_STATE_EXPORT).is_none() {
+            return Ok(None);
+        }
+        let fun = instance.get_typed_func::<(), (Vec<u8>,)>(&mut *store, SAVE_STATE_EXPORT)?;
+        let (state,) = fun.call(&mut *store, ())?;
+        Ok(Some(state))
+    }
+
+    /// Passes `state` to the `restore-state` export, returning `false` if the extension
+    /// doesn't have one.
+    pub fn restore_state(
+        &self,
+        store: &mut Store<ExtensionContext>,
+        state: Vec<u8>,
+    ) -> anyhow::Result<bool> {
+        let ExtensionInstance::Component(instance) = self else {
+            return Ok(false);
+        };
+        if instance
+            .get_func(&mut *store, RESTORE_STATE_EXPORT)
+            .is_none()
+        {
+            return Ok(false);
+        }
+        let fun = instance.get_typed_func::<(Vec<u8>,), ()>(&mut *store, RESTORE_STATE_EXPORT)?;
+        fun.call(&mut *store, (state,))?;
+        Ok(true)
+    }
+}
+
+impl WasmRuntime {
+    pub fn new(extension_folder: String, vulkan: VkBackend) -> anyhow::Result<Self> {
+        let host_config = HostConfig::load(Path::new(&extension_folder))?;
+        let mut config = engine_config(&host_config);
+        if host_config.pooling.enabled {
+            let pool = pool::pooling_config(Path::new(&extension_folder), &host_config.pooling);
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling(pool));
+        }
+        let engines = EngineLinkers::new(&config)?;
+        let tick_interval = if host_config.profiling.enabled {
+            Some(Duration::from_millis(
+                host_config.profiling.interval_ms.max(1),
+            ))
+        } else {
+            host_config.jobs.timeout().map(|_| JOB_TICK_INTERVAL)
+        };
+        let epoch_ticker = match tick_interval {
+            Some(interval) => {
+                let ticker = EpochTicker::start(interval)?;
+                ticker.add(&engines.engine);
+                Some(ticker)
+            }
+            None => None,
+        };
+        let cache = if host_config.cache.enabled {
+            Some(ArtifactCache::new(&host_config.cache)?)
+        } else {
+            None
+        };
+        let crash_reporter = if host_config.crash_reports.enabled {
+            Some(Arc::new(CrashReporter::new(&host_config)?))
+        } else {
+            None
+        };
+        let safe_mode = SafeMode::open(&host_config)?;
+        let policy = ExtensionPolicy::load(Path::new(&extension_folder))?;
+        let jobs = JobRunner::new(&host_config.jobs)?;
         Ok(Self {
-            linker,
-            engine,
+            engines,
+            on_demand: OnceLock::new(),
+            debug: OnceLock::new(),
             loaded_extensions: Arc::new(Mutex::new(HashMap::new())),
             registry: Arc::new(Mutex::new(HashMap::new())),
             extension_folder,
+            policy,
+            config: host_config,
+            cache,
             vulkan,
             enabled_vulkan_features: Arc::new(Mutex::new(HashSet::new())),
             enabled_vulkan_extensions: Arc::new(Mutex::new(HashSet::new())),
+            locale: Arc::new(Mutex::new(DEFAULT_LOCALE.to_string())),
+            watcher: Mutex::new(None),
+            jobs,
+            epoch_ticker,
+            scheduler: Arc::new(Mutex::new(Scheduler::default())),
+            bus: Arc::new(Mutex::new(Bus::default())),
+            crash_reporter,
+            safe_mode,
         })
     }
 
     pub fn load_extension(&self, file_name: &str, args: LaunchArgs) -> anyhow::Result<()> {
         let path = PathBuf::new().join(&self.extension_folder).join(file_name);
         let bytes = std::fs::read(path)?;
-        self.load_extension_by_bytes(&bytes, args)?;
-        Ok(())
+        self.load_package(&bytes, args, Some(file_name))
     }
 
     pub fn load_extension_by_bytes(&self, bytes: &[u8], args: LaunchArgs) -> anyhow::Result<()> {
+        self.load_package(bytes, args, None)
+    }
+
+    fn load_package(
+        &self,
+        bytes: &[u8],
+        args: LaunchArgs,
+        file_name: Option<&str>,
+    ) -> anyhow::Result<()> {
         let package = parse_package(bytes)?;
-        let wasm_bytes = package
-            .files
-            .get(package.manifest.entrypoint.as_str())
-            .ok_or(anyhow::anyhow!(
-                "Failed to find entrance wasm file in package"
-            ))?;
-        let wasm_component = Component::from_binary(&self.engine, wasm_bytes)?;
+        let identifier = ExtensionIdentifier::from_manifest(&package.manifest);
+        self.check_collision(&identifier)?;
+        let (store, instance) = self.instantiate_package(package, args)?;
+        self.insert_extension(store, instance, file_name)
+    }
+
+    /// Loads a batch of packages from the extension folder. Packages are parsed and compiled
+    /// in parallel, then instantiated one by one so that every extension comes after the
+    /// extensions it depends on. Returns one result per request, in request order.
+    pub fn load_extensions(&self, requests: Vec<(String, LaunchArgs)>) -> Vec<LoadResult> {
+        let folder = PathBuf::from(&self.extension_folder);
+        let (engine, policy, config, cache) = (
+            &self.engines.engine,
+            &self.policy,
+            &self.config,
+            self.cache.as_ref(),
+        );
+        let (debug_engines, ticker) = (&self.debug, self.epoch_ticker.as_ref());
+        let safe_mode = &self.safe_mode;
+        let compiled: Vec<_> = requests
+            .par_iter()
+            .map(|(file_name, _)| -> anyhow::Result<_> {
+                let package = parse_package(&std::fs::read(folder.join(file_name))?)?;
+                let identifier = ExtensionIdentifier::from_manifest(&package.manifest);
+                policy.check(&identifier, &package.hash, package.verified)?;
+                safe_mode.check(&identifier)?;
+                let engine = if debug::is_enabled(config, &package.manifest) {
+                    &debug_engines_for(debug_engines, config, ticker)?.engine
+                } else {
+                    engine
+                };
+                let binary = compile_package(engine, config, cache, &package)?;
+                Ok((package, binary))
+            })
+            .collect();
+
+        let mut results: Vec<Option<anyhow::Result<ExtensionIdentifier>>> =
+            requests.iter().map(|_| None).collect();
+        let mut pending = Vec::new();
+        for (index, result) in compiled.into_iter().enumerate() {
+            match result {
+                Ok(compiled) => pending.push((index, compiled)),
+                Err(err) => results[index] = Some(Err(err)),
+            }
+        }
+        let manifests: Vec<&ExtensionManifest> = pending
+            .iter()
+            .map(|(_, (package, _))| &package.manifest)
+            .collect();
+        let loaded: Vec<ExtensionIdentifier> = self
+            .loaded_extensions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|extension| extension.identifier.clone())
+            .collect();
+        let (order, failures) = dependency_order(&manifests, &loaded);
+        for (position, err) in failures {
+            results[pending[position].0] = Some(Err(err));
+        }
+
+        let mut pending: Vec<Option<(usize, (ExtensionPackage, ExtensionBinary))>> =
+            pending.into_iter().map(Some).collect();
+        let mut failed = HashSet::new();
+        for position in order {
+            let (index, (package, binary)) = pending[position].take().unwrap();
+            let identifier = ExtensionIdentifier::from_manifest(&package.manifest);
+            let result = package
+                .manifest
+                .requirements()
+                .and_then(|requirements| {
+                    match requirements
+                        .iter()
+                        .find(|requirement| failed.iter().any(|id| requirement.matches(id)))
+                    {
+                        Some(requirement) => {
+                            Err(anyhow!("Dependency {} failed to load", requirement))
+                        }
+                        None => Ok(()),
+                    }
+                })
+                .and_then(|_| self.check_collision(&identifier))
+                .and_then(|_| {
+                    let args = requests[index].1.clone();
+                    self.instantiate_binary(package, binary, args)
+                })
+                .and_then(|(store, instance)| {
+                    self.insert_extension(store, instance, Some(&requests[index].0))
+                });
+            results[index] = Some(match result {
+                Ok(()) => Ok(identifier),
+                Err(err) => {
+                    failed.insert(identifier);
+                    Err(err)
+                }
+            });
+        }
+
+        requests
+            .into_iter()
+            .zip(results)
+            .map(|((file_name, _), result)| match result {
+                Some(Ok(identifier)) => LoadResult {
+                    file_name,
+                    identifier: Some(identifier.to_string()),
+                    error: None,
+                },
+                Some(Err(err)) => LoadResult {
+                    file_name,
+                    identifier: None,
+                    error: Some(format!("{:#}", err)),
+                },
+                None => unreachable!("every request is either compiled or failed"),
+            })
+            .collect()
+    }
+
+    /// Registers an instantiated extension, rejecting it if its `namespace:id` is taken.
+    fn insert_extension(
+        &self,
+        store: Store<ExtensionContext>,
+        instance: ExtensionInstance,
+        file_name: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let extension = LoadedExtension::new(store, instance, file_name.map(str::to_string));
+        let mut loaded_extensions = self.loaded_extensions.lock().unwrap();
+        match loaded_extensions.entry(extension.identifier.unversioned()) {
+            Entry::Occupied(entry) => {
+                return Err(collision_error(
+                    &extension.identifier,
+                    &entry.get().identifier,
+                ));
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Arc::new(extension));
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up a loaded extension, holding the map lock only for the lookup.
+    fn get_extension(&self, id: &str) -> anyhow::Result<Arc<LoadedExtension>> {
+        let binding = self.loaded_extensions.lock().unwrap();
+        let key = resolve_key(&binding, id)?;
+        Ok(binding[&key].clone())
+    }
+
+    /// Removes `extension` from the loaded extensions, unless it was replaced meanwhile.
+    fn remove_extension(&self, extension: &Arc<LoadedExtension>) {
+        let key = extension.identifier.unversioned();
+        let mut binding = self.loaded_extensions.lock().unwrap();
+        if binding
+            .get(&key)
+            .is_some_and(|current| Arc::ptr_eq(current, extension))
+        {
+            binding.remove(&key);
+        }
+    }
+
+    /// Compiles and instantiates a package in a fresh store without registering it.
+    fn instantiate_package(
+        &self,
+        package: ExtensionPackage,
+        args: LaunchArgs,
+    ) -> anyhow::Result<(Store<ExtensionContext>, ExtensionInstance)> {
+        let identifier = ExtensionIdentifier::from_manifest(&package.manifest);
+        self.policy
+            .check(&identifier, &package.hash, package.verified)?;
+        self.safe_mode.check(&identifier)?;
+        let binary = self.compile(&package)?;
+        self.instantiate_binary(package, binary, args)
+    }
+
+    /// Instantiates an already compiled package in a fresh store without registering it.
+    /// When the instance pool is exhausted, the package is compiled again for an engine with
+    /// on-demand allocation and instantiated there instead.
+    fn instantiate_binary(
+        &self,
+        package: ExtensionPackage,
+        binary: ExtensionBinary,
+        args: LaunchArgs,
+    ) -> anyhow::Result<(Store<ExtensionContext>, ExtensionInstance)> {
+        let package = Arc::new(package);
+        let engines = self.engines_for(&package.manifest)?;
+        let services = self.resolve_services(&engines.engine, &package, &binary)?;
+        let mut store =
+            self.new_store(&engines.engine, package, &binary, &args, services.clone())?;
+        match engines.instantiate(&mut store, &binary, &self.loaded_extensions) {
+            Ok(instance) => {
+                store.data_mut().instance = Some(instance);
+                Ok((store, instance))
+            }
+            Err(err) if pool::is_pool_exhausted(&err) => {
+                let package = store.into_data().package;
+                warn!(
+                    "Instance pool exhausted, loading {} with on-demand allocation",
+                    package.manifest.id
+                );
+                let engines = self.on_demand()?;
+                let binary =
+                    compile_package(&engines.engine, &self.config, self.cache.as_ref(), &package)?;
+                let mut store =
+                    self.new_store(&engines.engine, package, &binary, &args, services)?;
+                let instance = engines.instantiate(&mut store, &binary, &self.loaded_extensions)?;
+                store.data_mut().instance = Some(instance);
+                Ok((store, instance))
+            }
+            Err(err) => Err(err),
+        }
+    }
 
+    fn resolve_services(
+        &self,
+        engine: &Engine,
+        package: &ExtensionPackage,
+        binary: &ExtensionBinary,
+    ) -> anyhow::Result<Vec<ServiceImport>> {
+        match binary {
+            ExtensionBinary::Component(component) => service::resolve_imports(
+                engine,
+                component,
+                &package.manifest,
+                &self.loaded_extensions.lock().unwrap(),
+            ),
+            ExtensionBinary::Module(_) => Ok(Vec::new()),
+        }
+    }
+
+    fn on_demand(&self) -> anyhow::Result<&EngineLinkers> {
+        if let Some(engines) = self.on_demand.get() {
+            return Ok(engines);
+        }
+        let engines = EngineLinkers::new(&engine_config(&self.config))?;
+        let mut created = false;
+        let engines = self.on_demand.get_or_init(|| {
+            created = true;
+            engines
+        });
+        if created && let Some(ticker) = &self.epoch_ticker {
+            ticker.add(&engines.engine);
+        }
+        Ok(engines)
+    }
+
+    /// Engine the extension of `manifest` is compiled for and runs in.
+    fn engines_for(&self, manifest: &ExtensionManifest) -> anyhow::Result<&EngineLinkers> {
+        if debug::is_enabled(&self.config, manifest) {
+            debug_engines_for(&self.debug, &self.config, self.epoch_ticker.as_ref())
+        } else {
+            Ok(&self.engines)
+        }
+    }
+
+    /// Creates the store an extension runs in, with WASI set up from its launch arguments.
+    fn new_store(
+        &self,
+        engine: &Engine,
+        package: Arc<ExtensionPackage>,
+        binary: &ExtensionBinary,
+        args: &LaunchArgs,
+        services: Vec<ServiceImport>,
+    ) -> anyhow::Result<Store<ExtensionContext>> {
+        let identifier = ExtensionIdentifier::from_manifest(&package.manifest);
         let mut wasi_builder = WasiCtxBuilder::new();
         wasi_builder.allow_blocking_current_thread(true);
         let mut network_addrs: Vec<(IpAddr, Option<u16>)> = Vec::new();
@@ -134,96 +692,1127 @@ impl WasmRuntime {
             });
         }
 
+        let world_version = match binary {
+            ExtensionBinary::Component(component) => {
+                compat::detect_world_version(engine, component)?
+            }
+            ExtensionBinary::Module(_) => WorldVersion::LATEST,
+        };
+        let (wasi_ctx, wasi_p1) = match binary {
+            ExtensionBinary::Component(_) => (wasi_builder.build(), None),
+            ExtensionBinary::Module(_) => {
+                (WasiCtxBuilder::new().build(), Some(wasi_builder.build_p1()))
+            }
+        };
+        let mut limits = StoreLimitsBuilder::new();
+        if let Some(max_memory) = package.manifest.runtime.max_memory {
+            limits = limits.memory_size(usize::try_from(max_memory).unwrap_or(usize::MAX));
+        }
+        let metrics = SharedMetrics::default();
+        let debug = debug::is_enabled(&self.config, &package.manifest);
         let mut store = Store::new(
-            &self.engine,
+            engine,
             ExtensionContext {
+                identifier,
+                store_id: NEXT_STORE_ID.fetch_add(1, Ordering::Relaxed),
                 package,
-                wasm_component: wasm_component.clone(),
-                wasi_ctx: wasi_builder.build(),
+                binary: binary.clone(),
+                world_version,
+                wasi_ctx,
+                wasi_p1,
                 table: ResourceTable::new(),
+                limits: MeteredLimits::new(limits.build(), metrics.clone()),
+                metrics,
                 instance: None,
                 public_registry: self.registry.clone(),
+                scheduler: self.scheduler.clone(),
+                bus: self.bus.clone(),
                 enabled_vulkan_features: self.enabled_vulkan_features.clone(),
                 enabled_vulkan_extensions: self.enabled_vulkan_extensions.clone(),
+                locale: self.locale.clone(),
+                launch_args: args.clone(),
+                background: false,
+                services,
+                profile: None,
+                debug,
+                recent_logs: RecentLogs::default(),
+                crash_reporter: self.crash_reporter.clone(),
             },
         );
-        let instance = self.linker.instantiate(&mut store, &wasm_component)?;
-        store.data_mut().instance = Some(instance);
-        let mut loaded_extensions = self.loaded_extensions.lock().unwrap();
-        loaded_extensions.insert(store.data().package.manifest.id.clone(), (store, instance));
-        Ok(())
+        store.limiter(|data| &mut data.limits);
+        if self.config.metrics.fuel {
+            store.set_fuel(u64::MAX)?;
+        }
+        if uses_epochs(&self.config) {
+            epoch::configure_store(&mut store);
+        }
+        if self.config.profiling.enabled {
+            profiling::configure_store(&mut store);
+        }
+        Ok(store)
+    }
+
+    fn compile(&self, package: &ExtensionPackage) -> anyhow::Result<ExtensionBinary> {
+        let engine = &self.engines_for(&package.manifest)?.engine;
+        compile_package(engine, &self.config, self.cache.as_ref(), package)
+    }
+
+    /// Rejects a package whose `namespace:id` is already taken by a loaded extension.
+    fn check_collision(&self, identifier: &ExtensionIdentifier) -> anyhow::Result<()> {
+        let loaded_extensions = self.loaded_extensions.lock().unwrap();
+        match loaded_extensions.get(&identifier.unversioned()) {
+            Some(loaded) => Err(collision_error(identifier, &loaded.identifier)),
+            None => Ok(()),
+        }
     }
 
     pub fn initialize_extension(&self, id: &str) -> anyhow::Result<()> {
-        let mut binding = self.loaded_extensions.lock().unwrap();
-        let (store, instance) = binding
-            .get_mut(id)
-            .ok_or(anyhow::anyhow!("Failed to find extension with id: {}", id))?;
+        let extension = self.get_extension(id)?;
+        let mut guard = extension.lock()?;
+        let (store, instance) = &mut *guard;
         let fun_name = store.data().package.manifest.entry_function.clone();
-        if let Some(fun) = instance.get_func(&mut *store, &fun_name) {
-            fun.call(store, &[], &mut [])?;
-        }
+        let locale = self.current_locale();
+        let _sentinel = self.safe_mode.initializing(&extension.identifier);
+        call_guest(store, metrics::INITIALIZE_TRIGGER, |store| {
+            instance.call_export(store, &fun_name)
+        })
+        .with_context(|| {
+            format!(
+                "Failed to initialize extension {}",
+                store.data().package.localized_name(&locale)
+            )
+        })?;
         Ok(())
     }
 
     pub fn initialize_extensions(&self) -> anyhow::Result<()> {
-        self.loaded_extensions
+        let locale = self.current_locale();
+        let extensions: Vec<Arc<LoadedExtension>> = self
+            .loaded_extensions
             .lock()
             .unwrap()
-            .iter_mut()
-            .for_each(|(_name, (store, instance))| {
-                let fun_name = store.data().package.manifest.entry_function.clone();
-                if let Some(fun) = instance.get_func(&mut *store, &fun_name) {
-                    let result = fun.call(store, &[], &mut []);
-                    if let Err(result) = result {
-                        error!("Failed to initialize extension: {:?}", result)
-                    }
-                };
-            });
+            .values()
+            .cloned()
+            .collect();
+        for extension in extensions {
+            let result = extension
+                .lock()
+                .map_err(anyhow::Error::from)
+                .and_then(|mut guard| {
+                    let (store, instance) = &mut *guard;
+                    let fun_name = store.data().package.manifest.entry_function.clone();
+                    let _sentinel = self.safe_mode.initializing(&extension.identifier);
+                    call_guest(store, metrics::INITIALIZE_TRIGGER, |store| {
+                        instance.call_export(store, &fun_name)
+                    })
+                });
+            if let Err(result) = result {
+                error!(
+                    "Failed to initialize extension {}: {:?}",
+                    extension.package.localized_name(&locale),
+                    result
+                )
+            }
+        }
         Ok(())
     }
 
-    pub fn disable_extension(&self, id: &str) -> anyhow::Result<()> {
+    pub fn current_locale(&self) -> String {
+        self.locale
+            .lock()
+            .map_or(DEFAULT_LOCALE.to_string(), |locale| locale.clone())
+    }
+
+    /// Describes every loaded extension with its name and description in the current locale.
+    pub fn list_extensions(&self) -> Vec<ExtensionInfo> {
+        let locale = self.current_locale();
+        let mut infos: Vec<ExtensionInfo> = self
+            .loaded_extensions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|extension| {
+                let package = &extension.package;
+                ExtensionInfo {
+                    identifier: extension.identifier.to_string(),
+                    id: package.manifest.id.clone(),
+                    namespace: package.manifest.namespaces.clone(),
+                    version: package.manifest.version.clone(),
+                    name: package.localized_name(&locale),
+                    description: package.localized_description(&locale),
+                    hash: package.hash.to_string(),
+                    verified: package.verified,
+                }
+            })
+            .collect();
+        infos.sort_by(|a, b| (&a.namespace, &a.id).cmp(&(&b.namespace, &b.id)));
+        infos
+    }
+
+    /// Decodes the icon named by the extension's manifest, scaled to fit `size` pixels
+    /// unless `size` is 0. Sizes above [`MAX_ICON_DIMENSION`] are rejected.
+    pub fn extension_icon(&self, id: &str, size: u32) -> anyhow::Result<Icon> {
+        if size > MAX_ICON_DIMENSION {
+            return Err(anyhow!(
+                "Icon size {} is above the maximum of {}",
+                size,
+                MAX_ICON_DIMENSION
+            ));
+        }
+        let extension = self.get_extension(id)?;
+        let package = &extension.package;
+        let icon_path = package
+            .manifest
+            .icon
+            .as_deref()
+            .ok_or(anyhow::anyhow!("Extension {} has no icon", id))?;
+        let data = package.files.get(icon_path).ok_or(anyhow::anyhow!(
+            "Icon file not found in package: {}",
+            icon_path
+        ))?;
+        let icon = Icon::decode_png(data)
+            .with_context(|| format!("Failed to decode icon of extension {}", id))?;
+        Ok(if size == 0 {
+            icon
+        } else {
+            icon.scale_to_fit(size)
+        })
+    }
+
+    /// Replaces a loaded extension with a higher version of the same `namespace:id`, keeping
+    /// its launch arguments. The old instance saves its state and is closed, then the new one
+    /// is migrated from the old version and initialized. If any step fails, the old instance
+    /// gets its saved state back through `restore-state`, is initialized again and stays
+    /// loaded.
+    pub fn upgrade_extension(&self, id: &str, file_name: &str) -> anyhow::Result<()> {
+        let path = PathBuf::new().join(&self.extension_folder).join(file_name);
+        let package = parse_package(&std::fs::read(path)?)?;
+        let new_identifier = ExtensionIdentifier::from_manifest(&package.manifest);
+
+        let extension = self.get_extension(id)?;
+        let key = extension.identifier.unversioned();
+        let old_identifier = extension.identifier.clone();
+        let args = extension.lock()?.0.data().launch_args.clone();
+        if !new_identifier.same_identity(&old_identifier) {
+            return Err(anyhow!(
+                "Cannot upgrade {}: package contains {}",
+                old_identifier,
+                new_identifier
+            ));
+        }
+        match (old_identifier.semver(), new_identifier.semver()) {
+            (Some(old), Some(new)) if new > old => {}
+            _ => {
+                return Err(anyhow!(
+                    "Cannot upgrade {} to {}: the new version must be a higher semver version",
+                    old_identifier,
+                    new_identifier
+                ));
+            }
+        }
+        let (mut new_store, new_instance) = self.instantiate_package(package, args)?;
+
+        let mut guard = extension.lock()?;
+        let (old_store, old_instance) = &mut *guard;
+        let state = self.take_state(old_store, old_instance);
+        let result = self
+            .disable_inner(old_store, old_instance, &key)
+            .and_then(|_| {
+                let _sentinel = self.safe_mode.initializing(&new_identifier);
+                self.migrate(&mut new_store, &new_instance, &old_identifier)?;
+                let entry_fn = new_store.data().package.manifest.entry_function.clone();
+                call_guest(&mut new_store, metrics::INITIALIZE_TRIGGER, |store| {
+                    new_instance.call_export(store, &entry_fn)
+                })
+                .map(|_| ())
+            });
+        if let Err(err) = result {
+            self.registry
+                .lock()
+                .unwrap()
+                .retain(|_, registration| registration.owner != key);
+            self.scheduler.lock().unwrap().cancel_owned(&key);
+            self.bus.lock().unwrap().unsubscribe_owned(&key);
+            if let Err(restore_err) = self.reopen(old_store, old_instance, state) {
+                error!(
+                    "Failed to re-initialize {} after a failed upgrade: {:?}",
+                    old_identifier, restore_err
+                );
+            }
+            return Err(err.context(format!(
+                "Failed to upgrade {} to {}, kept the previous version",
+                old_identifier, new_identifier
+            )));
+        }
         let mut binding = self.loaded_extensions.lock().unwrap();
-        let (store, instance) = binding
-            .get_mut(id)
-            .ok_or(anyhow::anyhow!("Extension not found: {}", id))?;
-        self.disable_inner(store, instance, id)
+        match binding.get_mut(&key) {
+            Some(current) if Arc::ptr_eq(current, &extension) => {
+                *current = Arc::new(LoadedExtension::new(
+                    new_store,
+                    new_instance,
+                    Some(file_name.to_string()),
+                ));
+                Ok(())
+            }
+            _ => Err(anyhow!(
+                "Extension {} was unloaded during the upgrade",
+                old_identifier
+            )),
+        }
+    }
+
+    /// Calls the manifest's `migrate_function`, if any, with the version being replaced.
+    fn migrate(
+        &self,
+        store: &mut Store<ExtensionContext>,
+        instance: &ExtensionInstance,
+        previous: &ExtensionIdentifier,
+    ) -> anyhow::Result<()> {
+        let Some(migrate_fn) = store.data().package.manifest.migrate_function.clone() else {
+            return Ok(());
+        };
+        let previous_version = previous.version.clone().unwrap_or_default();
+        match instance {
+            ExtensionInstance::Component(instance) => {
+                let fun = instance.get_typed_func::<(String,), ()>(&mut *store, &migrate_fn)?;
+                fun.call(&mut *store, (previous_version,))?;
+                Ok(())
+            }
+            ExtensionInstance::Module(_) => Err(anyhow!(
+                "migrate_function is only supported by component extensions"
+            )),
+        }
+    }
+
+    pub fn disable_extension(&self, id: &str) -> anyhow::Result<()> {
+        let extension = self.get_extension(id)?;
+        let mut guard = extension.lock()?;
+        let (store, instance) = &mut *guard;
+        self.disable_inner(store, instance, &extension.identifier.unversioned())
     }
 
     fn disable_inner(
         &self,
         store: &mut Store<ExtensionContext>,
-        instance: &mut Instance,
-        id: &str,
+        instance: &mut ExtensionInstance,
+        key: &ExtensionIdentifier,
     ) -> anyhow::Result<()> {
         self.registry
             .lock()
             .unwrap()
-            .retain(|_, (_, ext_id)| ext_id != id);
+            .retain(|_, registration| &registration.owner != key);
+        self.scheduler.lock().unwrap().cancel_owned(key);
+        self.bus.lock().unwrap().unsubscribe_owned(key);
         if let Some(close_fn) = &store.data().package.manifest.close_function {
             let close_fn = close_fn.clone();
-            if let Some(fun) = instance.get_func(&mut *store, close_fn) {
-                fun.call(store, &[], &mut [])?;
-            }
+            let locale = self.current_locale();
+            call_guest(store, metrics::CLOSE_TRIGGER, |store| {
+                instance.call_export(store, &close_fn)
+            })
+            .with_context(|| {
+                format!(
+                    "Failed to close extension {}",
+                    store.data().package.localized_name(&locale)
+                )
+            })?;
         }
         Ok(())
     }
 
     pub fn unload_extension(&self, id: &str) -> anyhow::Result<()> {
-        let mut binding = self
+        let extension = self.get_extension(id)?;
+        self.check_dependents(&extension)?;
+        {
+            let mut guard = extension.lock()?;
+            let (store, instance) = &mut *guard;
+            self.disable_inner(store, instance, &extension.identifier.unversioned())?;
+        }
+        self.remove_extension(&extension);
+        Ok(())
+    }
+
+    /// Starts recording a guest profile of an extension, sampled on every epoch tick.
+    pub fn start_profiling(&self, id: &str) -> anyhow::Result<()> {
+        if !self.config.profiling.enabled {
+            return Err(anyhow!(
+                "Profiling is disabled, enable it under `profiling` in host.json"
+            ));
+        }
+        let extension = self.get_extension(id)?;
+        let mut guard = extension.lock()?;
+        profiling::start(&mut guard.0, &self.config.profiling)
+    }
+
+    /// Stops profiling an extension and writes the profile, returning the file's path.
+    pub fn stop_profiling(&self, id: &str) -> anyhow::Result<PathBuf> {
+        let extension = self.get_extension(id)?;
+        let mut guard = extension.lock()?;
+        profiling::finish(&mut guard.0, &self.config.profiling)
+    }
+
+    /// Snapshots the call metrics of every loaded extension. Metrics are kept per loaded
+    /// instance, so reloading or upgrading an extension starts them over.
+    pub fn extension_metrics(&self) -> Vec<ExtensionMetricsInfo> {
+        let mut infos: Vec<ExtensionMetricsInfo> = self
+            .loaded_extensions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|extension| ExtensionMetricsInfo {
+                identifier: extension.identifier.to_string(),
+                metrics: extension.metrics.lock().unwrap().clone(),
+            })
+            .collect();
+        infos.sort_by(|a, b| a.identifier.cmp(&b.identifier));
+        infos
+    }
+
+    /// Resets the metrics of one extension, or of every loaded extension if `id` is `None`.
+    pub fn reset_metrics(&self, id: Option<&str>) -> anyhow::Result<()> {
+        match id {
+            Some(id) => self.get_extension(id)?.metrics.lock().unwrap().reset(),
+            None => {
+                for extension in self.loaded_extensions.lock().unwrap().values() {
+                    extension.metrics.lock().unwrap().reset();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Extensions skipped because the process crashed while they were initializing.
+    pub fn skipped_extensions(&self) -> SkippedExtensions {
+        self.safe_mode.skipped()
+    }
+
+    /// Lets an extension skipped in safe mode load again, or all of them if `id` is `None`.
+    /// Re-enabled extensions are loaded by the next load request for their package.
+    pub fn reenable_extension(&self, id: Option<&str>) -> anyhow::Result<()> {
+        self.safe_mode.reenable(id)
+    }
+
+    /// Summarizes the crash reports written by this and earlier runs, newest first.
+    pub fn crash_reports(&self) -> anyhow::Result<Vec<CrashSummary>> {
+        match &self.crash_reporter {
+            Some(reporter) => reporter.list(),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Rejects unloading `extension` while loaded extensions import its services.
+    fn check_dependents(&self, extension: &LoadedExtension) -> anyhow::Result<()> {
+        let key = extension.identifier.unversioned();
+        let mut dependents: Vec<String> = self
             .loaded_extensions
             .lock()
-            .map_err(|err| anyhow!("Failed to lock loaded extensions: {}", err))?;
-        if let Some((store, instance)) = binding.get_mut(id) {
-            self.disable_inner(store, instance, id)?;
+            .unwrap()
+            .values()
+            .filter(|dependent| {
+                dependent
+                    .services
+                    .iter()
+                    .any(|service| service.provider == key)
+            })
+            .map(|dependent| dependent.identifier.to_string())
+            .collect();
+        if dependents.is_empty() {
+            return Ok(());
+        }
+        dependents.sort();
+        Err(anyhow!(
+            "Cannot unload {}: {} use its services",
+            extension.identifier,
+            dependents.join(", ")
+        ))
+    }
+
+    /// Calls the handler registered for `trigger` on the current thread.
+    pub fn dispatch(&self, trigger: &str) -> anyhow::Result<()> {
+        let registration = self.registration(trigger)?;
+        call_registered(&self.loaded_extensions, trigger, &registration)
+    }
+
+    /// Queues the handler registered for `trigger` on the worker pool and returns the job
+    /// id. Only handlers the manifest marks as background-safe may run this way. With
+    /// `jobs.timeout_ms` set in the host config, a job running longer fails; the extension's
+    /// instance may be left mid-call then and should be reloaded.
+    pub fn dispatch_background(&self, trigger: &str) -> anyhow::Result<i64> {
+        let registration = self.registration(trigger)?;
+        if registration.affinity != HandlerAffinity::Background {
+            return Err(anyhow!(
+                "Handler of {} for '{}' may only run on the render thread",
+                registration.owner,
+                trigger
+            ));
+        }
+        let loaded_extensions = self.loaded_extensions.clone();
+        let trigger = trigger.to_string();
+        let timeout = self.config.jobs.timeout();
+        Ok(self.jobs.spawn(move || {
+            call_background(&loaded_extensions, &trigger, &registration, timeout)
+                .with_context(|| format!("Background handler for '{}' failed", trigger))
+        }))
+    }
+
+    /// Delivers the bus messages queued since the last tick, then advances the scheduler to
+    /// `now`, in milliseconds of the host's clock, and runs the scheduled callbacks that are
+    /// due, all on the calling thread. A scheduled callback that fails is cancelled; the
+    /// failures are returned after every delivery and callback has run. Callbacks scheduled
+    /// by an instance that was since reloaded or upgraded are cancelled, and messages queued
+    /// for one are dropped, without failing.
+    pub fn tick(&self, now: u64) -> Vec<anyhow::Error> {
+        let mut errors = Vec::new();
+        let deliveries = self.bus.lock().unwrap().drain();
+        for delivery in deliveries {
+            let trigger = format!("{}{}", metrics::BUS_TRIGGER_PREFIX, delivery.channel);
+            let result = call_loaded(
+                &self.loaded_extensions,
+                &delivery.owner,
+                delivery.store_id,
+                &trigger,
+                |store| {
+                    let params = (delivery.channel.clone(), delivery.payload);
+                    Ok(delivery.handler.call(&mut *store, params)?)
+                },
+            );
+            if let Err(err) = result {
+                // Queued for an instance that was replaced; the new one subscribes itself.
+                if err.is::<StaleHandler>() {
+                    debug!("Dropped a message on '{}': {}", delivery.channel, err);
+                    continue;
+                }
+                errors.push(err.context(format!(
+                    "Delivering a message on '{}' to {} failed",
+                    delivery.channel, delivery.owner
+                )));
+            }
+        }
+        let due: Vec<TaskHandle> = self.scheduler.lock().unwrap().due(now);
+        for handle in due {
+            let Some(registration) = self.scheduler.lock().unwrap().start(handle) else {
+                continue;
+            };
+            let result = call_registered(
+                &self.loaded_extensions,
+                metrics::SCHEDULER_TRIGGER,
+                &registration,
+            );
+            if let Err(err) = result {
+                self.scheduler.lock().unwrap().remove(handle);
+                // The task outlived its instance; the instance replacing it schedules its own.
+                if err.is::<StaleHandler>() {
+                    debug!("Dropped scheduled task {}: {}", handle, err);
+                    continue;
+                }
+                errors.push(err.context(format!(
+                    "Scheduled task {} of {} failed and was cancelled",
+                    handle, registration.owner
+                )));
+            }
+        }
+        errors
+    }
+
+    fn registration(&self, trigger: &str) -> anyhow::Result<Registration> {
+        self.registry
+            .lock()
+            .unwrap()
+            .get(trigger)
+            .cloned()
+            .ok_or(anyhow!("No handler registered for '{}'", trigger))
+    }
+
+    /// Starts or stops watching the extension folder for package changes.
+    pub fn watch_extensions(&self, enabled: bool) -> anyhow::Result<()> {
+        let mut watcher = self.watcher.lock().unwrap();
+        if !enabled {
+            *watcher = None;
+        } else if watcher.is_none() {
+            *watcher = Some(PackageWatcher::start(PathBuf::from(
+                &self.extension_folder,
+            ))?);
+        }
+        Ok(())
+    }
+
+    /// Applies the package changes seen by the watcher since the last call. Changed packages
+    /// go through disable, unload, load and initialize, and removed packages are disabled and
+    /// unloaded. Packages no extension was loaded from are only reported, so the caller can
+    /// load them with the launch arguments it chooses.
+    pub fn process_package_changes(&self) -> Vec<ReloadEvent> {
+        let changes = match self.watcher.lock().unwrap().as_ref() {
+            Some(watcher) => watcher.drain(),
+            None => return Vec::new(),
+        };
+        changes
+            .into_iter()
+            .map(|change| {
+                let (file_name, (identifier, result)) = match change {
+                    PackageChange::Added(file_name) | PackageChange::Changed(file_name) => {
+                        let result = self.reload_package(&file_name);
+                        (file_name, result)
+                    }
+                    PackageChange::Removed(file_name) => {
+                        let result = self.remove_package(&file_name);
+                        (file_name, result)
+                    }
+                };
+                let identifier = identifier.map(|identifier| identifier.to_string());
+                match result {
+                    Ok(kind) => ReloadEvent {
+                        file_name,
+                        kind,
+                        identifier,
+                        error: None,
+                    },
+                    Err(err) => {
+                        error!(
+                            "Failed to reload extension package {}: {:?}",
+                            file_name, err
+                        );
+                        ReloadEvent {
+                            file_name,
+                            kind: ReloadKind::Failed,
+                            identifier,
+                            error: Some(format!("{:#}", err)),
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Loads `file_name` again if an extension was loaded from it, and returns the identifier
+    /// of the package, or of the extension previously loaded from the file if the package
+    /// can't be read, along with the outcome.
+    fn reload_package(
+        &self,
+        file_name: &str,
+    ) -> (Option<ExtensionIdentifier>, anyhow::Result<ReloadKind>) {
+        let path = PathBuf::new().join(&self.extension_folder).join(file_name);
+        let previous = self.find_by_file(file_name);
+        let package = std::fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| parse_package(&bytes));
+        match (package, previous) {
+            (Ok(package), Some(previous)) => {
+                let identifier = ExtensionIdentifier::from_manifest(&package.manifest);
+                let result = self.replace_package(file_name, package, &identifier, previous);
+                (Some(identifier), result)
+            }
+            (Ok(package), None) => (
+                Some(ExtensionIdentifier::from_manifest(&package.manifest)),
+                Ok(ReloadKind::Added),
+            ),
+            (Err(err), previous) => (
+                previous.map(|previous| previous.identifier.clone()),
+                Err(err),
+            ),
+        }
+    }
+
+    /// Replaces the extension `previous` loaded from `file_name` with `package`. The new
+    /// build is compiled and linked before the old instance is touched, so a package that
+    /// fails to build leaves the old instance running. The old instance then saves its state
+    /// and is closed, and the state is restored into the new one before it is initialized.
+    /// The old instance is only unloaded once the new one has initialized; if either step
+    /// fails, the old instance gets its state back, is initialized again and stays loaded.
+    fn replace_package(
+        &self,
+        file_name: &str,
+        package: ExtensionPackage,
+        identifier: &ExtensionIdentifier,
+        previous: Arc<LoadedExtension>,
+    ) -> anyhow::Result<ReloadKind> {
+        let key = identifier.unversioned();
+        let previous_key = previous.identifier.unversioned();
+        if previous_key != key {
+            self.check_collision(identifier)?;
+        }
+        let args = previous.lock()?.0.data().launch_args.clone();
+        let (mut store, instance) = self.instantiate_package(package, args)?;
+
+        let mut guard = previous.lock()?;
+        let (old_store, old_instance) = &mut *guard;
+        let state = self.take_state(old_store, old_instance);
+        if let Err(err) = self.disable_inner(old_store, old_instance, &previous_key) {
+            error!(
+                "Failed to disable {} before reloading it: {:?}",
+                previous_key, err
+            );
+        }
+        let initialized = match state.clone() {
+            Some(state) => instance.restore_state(&mut store, state).with_context(|| {
+                format!(
+                    "Failed to restore the state of reloaded extension {}",
+                    identifier
+                )
+            }),
+            None => Ok(true),
+        }
+        .and_then(|restored| {
+            if !restored {
+                warn!(
+                    "Reloaded extension {} does not export {}, its previous state was dropped",
+                    identifier, RESTORE_STATE_EXPORT
+                );
+            }
+            let entry_fn = store.data().package.manifest.entry_function.clone();
+            let _sentinel = self.safe_mode.initializing(identifier);
+            call_guest(&mut store, metrics::INITIALIZE_TRIGGER, |store| {
+                instance.call_export(store, &entry_fn)
+            })
+        });
+        if let Err(err) = initialized {
+            self.registry
+                .lock()
+                .unwrap()
+                .retain(|_, registration| registration.owner != key);
+            self.scheduler.lock().unwrap().cancel_owned(&key);
+            self.bus.lock().unwrap().unsubscribe_owned(&key);
+            if let Err(restore_err) = self.reopen(old_store, old_instance, state) {
+                error!(
+                    "Failed to re-initialize {} after a failed reload: {:?}",
+                    previous_key, restore_err
+                );
+            }
+            return Err(err.context(format!(
+                "Failed to initialize reloaded extension {}, kept the previous instance",
+                identifier
+            )));
+        }
+        drop(guard);
+        let mut binding = self.loaded_extensions.lock().unwrap();
+        if !binding
+            .get(&previous_key)
+            .is_some_and(|current| Arc::ptr_eq(current, &previous))
+        {
+            return Err(anyhow!(
+                "Extension {} was unloaded during the reload",
+                previous_key
+            ));
+        }
+        binding.remove(&previous_key);
+        let extension = LoadedExtension::new(store, instance, Some(file_name.to_string()));
+        binding.insert(key, Arc::new(extension));
+        Ok(ReloadKind::Reloaded)
+    }
+
+    fn remove_package(
+        &self,
+        file_name: &str,
+    ) -> (Option<ExtensionIdentifier>, anyhow::Result<ReloadKind>) {
+        let Some(extension) = self.find_by_file(file_name) else {
+            return (None, Ok(ReloadKind::Unloaded));
+        };
+        let result = self.check_dependents(&extension).and_then(|_| {
+            self.remove_extension(&extension);
+            let mut guard = extension.lock()?;
+            let (store, instance) = &mut *guard;
+            self.disable_inner(store, instance, &extension.identifier.unversioned())?;
+            Ok(ReloadKind::Unloaded)
+        });
+        (Some(extension.identifier.clone()), result)
+    }
+
+    /// Saves the state of an instance about to be reloaded, dropping it with a warning if
+    /// saving fails or the state exceeds [`MAX_STATE_SIZE`].
+    fn take_state(
+        &self,
+        store: &mut Store<ExtensionContext>,
+        instance: &ExtensionInstance,
+    ) -> Option<Vec<u8>> {
+        let identifier = store.data().identifier.clone();
+        match instance.save_state(store) {
+            Ok(Some(state)) if state.len() > MAX_STATE_SIZE => {
+                warn!(
+                    "State of {} is {} bytes, over the {} byte limit; dropping it",
+                    identifier,
+                    state.len(),
+                    MAX_STATE_SIZE
+                );
+                None
+            }
+            Ok(state) => state,
+            Err(err) => {
+                warn!("Failed to save the state of {}: {:?}", identifier, err);
+                None
+            }
         }
-        binding.remove(id);
+    }
+
+    /// Brings back an instance closed for an upgrade or reload that failed: it gets its saved
+    /// state back through `restore-state` and is initialized again.
+    fn reopen(
+        &self,
+        store: &mut Store<ExtensionContext>,
+        instance: &ExtensionInstance,
+        state: Option<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        if let Some(state) = state {
+            instance.restore_state(store, state)?;
+        }
+        let entry_fn = store.data().package.manifest.entry_function.clone();
+        let _sentinel = self.safe_mode.initializing(&store.data().identifier);
+        call_guest(store, metrics::INITIALIZE_TRIGGER, |store| {
+            instance.call_export(store, &entry_fn)
+        })?;
         Ok(())
     }
+
+    /// Finds the extension loaded from `file_name`.
+    fn find_by_file(&self, file_name: &str) -> Option<Arc<LoadedExtension>> {
+        self.loaded_extensions
+            .lock()
+            .unwrap()
+            .values()
+            .find(|extension| extension.file_name.as_deref() == Some(file_name))
+            .cloned()
+    }
+}
+
+/// Produces the package's compiled entrypoint from, in order, a trusted precompiled
+/// artifact shipped in the package, the artifact cache, or compiling the entrypoint.
+/// Precompiled artifacts are skipped in debug mode, as they carry no debug info.
+fn compile_package(
+    engine: &Engine,
+    config: &HostConfig,
+    cache: Option<&ArtifactCache>,
+    package: &ExtensionPackage,
+) -> anyhow::Result<ExtensionBinary> {
+    if config.cache.trust_precompiled && !debug::is_enabled(config, &package.manifest) {
+        for name in &package.manifest.precompiled {
+            let Some(bytes) = package.files.get(name.as_str()) else {
+                warn!("Precompiled artifact {} not found in package", name);
+                continue;
+            };
+            // SAFETY: the host config opted into trusting precompiled package artifacts.
+            match unsafe { cache::deserialize(engine, bytes) } {
+                Ok(binary) => return Ok(binary),
+                Err(err) => debug!("Skipping precompiled artifact {}: {}", name, err),
+            }
+        }
+    }
+    if let Some(binary) = cache.and_then(|cache| cache.load(engine, &package.hash)) {
+        return Ok(binary);
+    }
+
+    let wasm_bytes = package
+        .files
+        .get(package.manifest.entrypoint.as_str())
+        .ok_or(anyhow::anyhow!(
+            "Failed to find entrance wasm file in package"
+        ))?;
+    let binary = match detect_binary_kind(wasm_bytes)? {
+        BinaryKind::Component => {
+            ExtensionBinary::Component(Component::from_binary(engine, wasm_bytes)?)
+        }
+        BinaryKind::Module => ExtensionBinary::Module(Module::from_binary(engine, wasm_bytes)?),
+    };
+    if let Some(cache) = cache
+        && let Err(err) = cache.store(engine, &package.hash, &binary)
+    {
+        warn!(
+            "Failed to cache compiled {}: {:?}",
+            package.manifest.id, err
+        );
+    }
+    Ok(binary)
 }
 
-#[derive(Debug, Default)]
+/// Orders `manifests` so that each comes after the ones it depends on. Dependencies on
+/// `loaded` extensions are satisfied directly; manifests with unsatisfied or cyclic
+/// dependencies are returned as failures instead of being ordered.
+fn dependency_order(
+    manifests: &[&ExtensionManifest],
+    loaded: &[ExtensionIdentifier],
+) -> (Vec<usize>, Vec<(usize, anyhow::Error)>) {
+    let identifiers: Vec<ExtensionIdentifier> = manifests
+        .iter()
+        .map(|manifest| ExtensionIdentifier::from_manifest(manifest))
+        .collect();
+
+    let mut failures = Vec::new();
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); manifests.len()];
+    let mut remaining: Vec<usize> = vec![0; manifests.len()];
+    let mut valid = vec![true; manifests.len()];
+    for (position, manifest) in manifests.iter().enumerate() {
+        let requirements = match manifest.requirements() {
+            Ok(requirements) => requirements,
+            Err(err) => {
+                failures.push((position, err));
+                valid[position] = false;
+                continue;
+            }
+        };
+        for requirement in requirements {
+            if loaded.iter().any(|id| requirement.matches(id)) {
+                continue;
+            }
+            match identifiers.iter().position(|id| requirement.matches(id)) {
+                Some(dependency) => {
+                    dependents[dependency].push(position);
+                    remaining[position] += 1;
+                }
+                None => {
+                    failures.push((position, anyhow!("Missing dependency {}", requirement)));
+                    valid[position] = false;
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut ready: Vec<usize> = (0..manifests.len())
+        .filter(|&position| valid[position] && remaining[position] == 0)
+        .collect();
+    let mut order = Vec::new();
+    while let Some(position) = ready.pop() {
+        order.push(position);
+        for &dependent in &dependents[position] {
+            remaining[dependent] -= 1;
+            if valid[dependent] && remaining[dependent] == 0 {
+                ready.push(dependent);
+            }
+        }
+    }
+    for position in 0..manifests.len() {
+        if valid[position] && remaining[position] > 0 {
+            failures.push((
+                position,
+                anyhow!(
+                    "Dependencies of {} failed to load or form a cycle",
+                    identifiers[position]
+                ),
+            ));
+        }
+    }
+    (order, failures)
+}
+
+/// Engine settings shared by the pooling and on-demand engines.
+fn engine_config(host_config: &HostConfig) -> Config {
+    let mut config = Config::new();
+    if uses_epochs(host_config) {
+        config.epoch_interruption(true);
+    }
+    if host_config.metrics.fuel {
+        config.consume_fuel(true);
+    }
+    config
+}
+
+/// Whether guests are interrupted on epoch ticks, for profiling or to time out background jobs.
+fn uses_epochs(host_config: &HostConfig) -> bool {
+    host_config.profiling.enabled || host_config.jobs.timeout().is_some()
+}
+
+/// Returns the debug engine, creating it on first use.
+fn debug_engines_for<'a>(
+    debug_engines: &'a OnceLock<EngineLinkers>,
+    config: &HostConfig,
+    ticker: Option<&EpochTicker>,
+) -> anyhow::Result<&'a EngineLinkers> {
+    if let Some(engines) = debug_engines.get() {
+        return Ok(engines);
+    }
+    let mut engine_config = engine_config(config);
+    debug::configure_engine(&mut engine_config);
+    let engines = EngineLinkers::new(&engine_config)?;
+    let mut created = false;
+    let engines = debug_engines.get_or_init(|| {
+        created = true;
+        engines
+    });
+    if created && let Some(ticker) = ticker {
+        ticker.add(&engines.engine);
+    }
+    Ok(engines)
+}
+
+fn call_registered(
+    loaded_extensions: &LoadedExtensions,
+    trigger: &str,
+    registration: &Registration,
+) -> anyhow::Result<()> {
+    call_loaded(
+        loaded_extensions,
+        &registration.owner,
+        registration.store_id,
+        trigger,
+        |store| registration.func.call(store),
+    )
+}
+
+/// Calls a background-safe handler asynchronously, so that with a job timeout configured it
+/// yields on every epoch tick and is abandoned once the timeout has passed.
+fn call_background(
+    loaded_extensions: &LoadedExtensions,
+    trigger: &str,
+    registration: &Registration,
+    timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    call_loaded(
+        loaded_extensions,
+        &registration.owner,
+        registration.store_id,
+        trigger,
+        |store| {
+            epoch::start_background(store);
+            let result = jobs::block_on(registration.func.call_async(&mut *store), timeout);
+            epoch::finish_background(store);
+            result?
+        },
+    )
+}
+
+/// Runs `call` with the store of the loaded extension `owner`, holding its lock, as a guest
+/// call for `trigger`, see [`call_guest`]. Fails with [`StaleHandler`] if `owner` was
+/// replaced since `store_id` was recorded, as its functions belong to the old store.
+fn call_loaded<R>(
+    loaded_extensions: &LoadedExtensions,
+    owner: &ExtensionIdentifier,
+    store_id: u64,
+    trigger: &str,
+    call: impl FnOnce(&mut Store<ExtensionContext>) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+    let extension = loaded_extensions
+        .lock()
+        .unwrap()
+        .get(owner)
+        .cloned()
+        .ok_or(anyhow!("Extension {} is not loaded", owner))?;
+    if extension.store_id != store_id {
+        return Err(StaleHandler {
+            identifier: extension.identifier.to_string(),
+        }
+        .into());
+    }
+    let mut guard = extension.lock()?;
+    call_guest(&mut guard.0, trigger, call)
+}
+
+/// Runs a guest call, recording it in the extension's metrics under `trigger` and writing a
+/// crash report if it fails.
+pub(crate) fn call_guest<R>(
+    store: &mut Store<ExtensionContext>,
+    trigger: &str,
+    call: impl FnOnce(&mut Store<ExtensionContext>) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+    let result = metrics::measure(store, trigger, call);
+    if let Err(err) = &result
+        && let Some(reporter) = &store.data().crash_reporter
+    {
+        let identifier = &store.data().identifier;
+        match reporter.report(store.data(), trigger, err) {
+            Ok(Some(path)) => warn!(
+                "Call of {} for '{}' failed, wrote crash report {}",
+                identifier,
+                trigger,
+                path.display()
+            ),
+            Ok(None) => debug!("Call of {} for '{}' failed again", identifier, trigger),
+            Err(report_err) => {
+                warn!(
+                    "Failed to write a crash report for {}: {:?}",
+                    identifier, report_err
+                )
+            }
+        }
+    }
+    result
+}
+
+/// Finds the loaded extension named by `query`: `namespace:id` (any `@version` suffix is
+/// ignored), or a bare `id` as long as only one namespace has an extension with that id.
+fn resolve_key<V>(
+    extensions: &HashMap<ExtensionIdentifier, V>,
+    query: &str,
+) -> anyhow::Result<ExtensionIdentifier> {
+    let name = query.split_once('@').map_or(query, |(name, _)| name);
+    if name.contains(':') {
+        let key = name.parse::<ExtensionIdentifier>()?;
+        return if extensions.contains_key(&key) {
+            Ok(key)
+        } else {
+            Err(anyhow!("Extension not found: {}", query))
+        };
+    }
+    let mut candidates: Vec<&ExtensionIdentifier> =
+        extensions.keys().filter(|key| key.id == name).collect();
+    match candidates.len() {
+        0 => Err(anyhow!("Extension not found: {}", query)),
+        1 => Ok(candidates[0].clone()),
+        _ => {
+            candidates.sort();
+            let names: Vec<String> = candidates.iter().map(|key| key.to_string()).collect();
+            Err(anyhow!(
+                "Extension id '{}' is ambiguous, use one of: {}",
+                query,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+fn collision_error(
+    identifier: &ExtensionIdentifier,
+    loaded: &ExtensionIdentifier,
+) -> anyhow::Error {
+    anyhow!("Cannot load {}: {} is already loaded", identifier, loaded)
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExtensionInfo {
+    pub identifier: String,
+    pub id: String,
+    pub namespace: String,
+    pub version: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub hash: String,
+    /// Whether the package's files matched its digest list, see [`ExtensionPackage::verified`].
+    pub verified: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExtensionMetricsInfo {
+    pub identifier: String,
+    #[serde(flatten)]
+    pub metrics: ExtensionMetrics,
+}
+
+/// Outcome of loading one package of a batch.
+#[derive(Debug, Serialize)]
+pub struct LoadResult {
+    pub file_name: String,
+    pub identifier: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Outcome of applying one package change seen by the extension folder watcher.
+#[derive(Debug, Serialize)]
+pub struct ReloadEvent {
+    pub file_name: String,
+    pub kind: ReloadKind,
+    pub identifier: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReloadKind {
+    /// A package no extension was loaded from appeared or changed; it is left to the caller
+    /// to load.
+    Added,
+    Reloaded,
+    Unloaded,
+    /// The package failed to build or its new instance failed to initialize, in which case
+    /// any instance loaded from it before keeps running.
+    Failed,
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct LaunchArgs {
     pub enabled_vulkan_extensions: Vec<String>,
     pub enabled_vulkan_features: Vec<String>,
@@ -317,3 +1906,60 @@ fn parse_wasi_feature(s: &str) -> anyhow::Result<WasiFeature> {
         Err(anyhow::anyhow!("Unknown WASI feature: {}", s))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(id: &str, version: &str, dependencies: &[&str]) -> ExtensionManifest {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "namespaces": "acme",
+            "version": version,
+            "dependencies": dependencies,
+        }))
+        .unwrap()
+    }
+
+    fn order(manifests: &[ExtensionManifest], loaded: &[&str]) -> (Vec<usize>, Vec<usize>) {
+        let manifests: Vec<&ExtensionManifest> = manifests.iter().collect();
+        let loaded: Vec<ExtensionIdentifier> = loaded
+            .iter()
+            .map(|identifier| identifier.parse().unwrap())
+            .collect();
+        let (order, failures) = dependency_order(&manifests, &loaded);
+        let mut failed: Vec<usize> = failures.into_iter().map(|(position, _)| position).collect();
+        failed.sort();
+        (order, failed)
+    }
+
+    #[test]
+    fn orders_dependencies_first() {
+        let manifests = [
+            manifest("app", "1.0.0", &["acme:ui@^1", "acme:core"]),
+            manifest("ui", "1.2.0", &["acme:core@>=2"]),
+            manifest("core", "2.1.0", &[]),
+        ];
+        assert_eq!(order(&manifests, &[]), (vec![2, 1, 0], vec![]));
+    }
+
+    #[test]
+    fn uses_loaded_extensions() {
+        let manifests = [manifest("ui", "1.0.0", &["acme:core@^2"])];
+        assert_eq!(order(&manifests, &["acme:core@2.0.0"]), (vec![0], vec![]));
+        assert_eq!(order(&manifests, &["acme:core@1.0.0"]), (vec![], vec![0]));
+    }
+
+    #[test]
+    fn fails_missing_and_cyclic_dependencies() {
+        let manifests = [
+            manifest("a", "1.0.0", &["acme:b"]),
+            manifest("b", "1.0.0", &["acme:a"]),
+            manifest("c", "1.0.0", &["acme:d"]),
+            manifest("e", "1.0.0", &["acme:c"]),
+            manifest("f", "1.0.0", &["not a requirement@"]),
+            manifest("g", "1.0.0", &[]),
+        ];
+        assert_eq!(order(&manifests, &[]), (vec![5], vec![0, 1, 2, 3, 4]));
+    }
+}
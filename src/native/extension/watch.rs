@@ -0,0 +1,129 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use log::warn;
+
+/// How often the extension folder is scanned for package changes.
+pub static POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+static PACKAGE_SUFFIX: &str = ".zip";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackageChange {
+    Added(String),
+    Changed(String),
+    Removed(String),
+}
+
+/// Background thread that scans the extension folder for added, changed and removed
+/// packages. A change is only reported once the file has stayed the same for a whole poll
+/// interval, so packages still being written are not picked up half-way.
+pub struct PackageWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    changes: Receiver<PackageChange>,
+}
+
+/// Modification time and size of a package file.
+type Stamp = (Option<SystemTime>, u64);
+
+impl PackageWatcher {
+    pub fn start(folder: PathBuf) -> anyhow::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, changes) = mpsc::channel();
+        let known = scan(&folder)?;
+        let thread = std::thread::Builder::new()
+            .name("ark-extension-watcher".to_string())
+            .spawn({
+                let stop = stop.clone();
+                move || watch(folder, known, sender, stop)
+            })?;
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+            changes,
+        })
+    }
+
+    /// Returns the changes seen since the last call, without blocking.
+    pub fn drain(&self) -> Vec<PackageChange> {
+        self.changes.try_iter().collect()
+    }
+}
+
+impl Drop for PackageWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch(
+    folder: PathBuf,
+    mut known: HashMap<String, Stamp>,
+    sender: Sender<PackageChange>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut previous = known.clone();
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(POLL_INTERVAL);
+        let current = match scan(&folder) {
+            Ok(current) => current,
+            Err(err) => {
+                warn!("Failed to scan {}: {}", folder.display(), err);
+                continue;
+            }
+        };
+        for (name, stamp) in &current {
+            if known.get(name) == Some(stamp) || previous.get(name) != Some(stamp) {
+                continue;
+            }
+            let change = match known.insert(name.clone(), *stamp) {
+                Some(_) => PackageChange::Changed(name.clone()),
+                None => PackageChange::Added(name.clone()),
+            };
+            if sender.send(change).is_err() {
+                return;
+            }
+        }
+        let removed: Vec<String> = known
+            .keys()
+            .filter(|name| !current.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in removed {
+            known.remove(&name);
+            if sender.send(PackageChange::Removed(name)).is_err() {
+                return;
+            }
+        }
+        previous = current;
+    }
+}
+
+fn scan(folder: &Path) -> std::io::Result<HashMap<String, Stamp>> {
+    let mut packages = HashMap::new();
+    for entry in std::fs::read_dir(folder)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.ends_with(PACKAGE_SUFFIX) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            packages.insert(name, (metadata.modified().ok(), metadata.len()));
+        }
+    }
+    Ok(packages)
+}
@@ -8,13 +8,17 @@ use std::sync::Mutex;
 use anyhow::anyhow;
 use mimalloc::MiMalloc;
 use vulkanalia::{
-    loader::{LibloadingLoader, LIBRARY},
-    vk, Entry,
+    Entry,
+    loader::{LIBRARY, LibloadingLoader},
+    vk,
 };
 use vulkanalia_vma::vma::VmaAllocator;
 
 use crate::{
-    extension::wasm::{LaunchArgs, WasmRuntime},
+    extension::{
+        jobs::{JOB_FAILED, JobCallback, JobStatus},
+        wasm::{LaunchArgs, WasmRuntime},
+    },
     vulkan::VkBackend,
 };
 
@@ -151,7 +155,10 @@ pub unsafe extern "C" fn ark_load_extension(
     };
     match ctx.wasm_runtime.load_extension(
         file_name.as_ref(),
-        LaunchArgs { enabled_wasi_features: wasi_features, ..Default::default() },
+        LaunchArgs {
+            enabled_wasi_features: wasi_features,
+            ..Default::default()
+        },
     ) {
         Ok(_) => 0,
         Err(e) => {
@@ -161,6 +168,56 @@ pub unsafe extern "C" fn ark_load_extension(
     }
 }
 
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// `requests_json` must be a valid C string holding a JSON array of
+/// `{"file_name": "...", "wasi_features": [...]}` objects.
+/// Compiles the packages in parallel and loads them in dependency order. Returns a
+/// heap-allocated C string holding a JSON array with one result per request, in request
+/// order, or null if the requests can't be parsed (use `ark_pop_error`).
+/// The caller must free the string via `ark_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_load_extensions(
+    ptr: i64,
+    requests_json: *const std::ffi::c_char,
+) -> *mut std::ffi::c_char {
+    #[derive(serde::Deserialize)]
+    struct LoadRequest {
+        file_name: String,
+        #[serde(default)]
+        wasi_features: Vec<String>,
+    }
+
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let json = unsafe { CStr::from_ptr(requests_json) }.to_string_lossy();
+    let requests: Vec<LoadRequest> = match serde_json::from_str(&json) {
+        Ok(v) => v,
+        Err(e) => {
+            ctx.push_error(anyhow::anyhow!("Failed to parse load requests JSON: {}", e));
+            return std::ptr::null_mut();
+        }
+    };
+    let requests = requests
+        .into_iter()
+        .map(|request| {
+            let args = LaunchArgs {
+                enabled_wasi_features: request.wasi_features,
+                ..Default::default()
+            };
+            (request.file_name, args)
+        })
+        .collect();
+    match serde_json::to_string(&ctx.wasm_runtime.load_extensions(requests)) {
+        Ok(json) => CString::new(json)
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            ctx.push_error(anyhow::anyhow!("Failed to serialize load results: {e}"));
+            std::ptr::null_mut()
+        }
+    }
+}
+
 /// # Safety
 /// `ptr` must be a pointer previously returned by `ark_create_native_context`.
 /// `id` must be a valid C string. Returns 0 on success, 1 on failure.
@@ -192,6 +249,91 @@ pub unsafe extern "C" fn ark_initialize_extensions(ptr: i64) -> i32 {
     }
 }
 
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// `trigger` must be a valid C string. Calls the handler registered for `trigger` on the
+/// calling thread. Returns 0 on success, 1 on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_dispatch(ptr: i64, trigger: *const std::ffi::c_char) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let trigger = unsafe { CStr::from_ptr(trigger) }.to_string_lossy();
+    match ctx.wasm_runtime.dispatch(&trigger) {
+        Ok(_) => 0,
+        Err(e) => {
+            ctx.push_error(e);
+            1
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// `trigger` must be a valid C string. Queues the background-safe handler registered for
+/// `trigger` on the native worker pool and returns its job id, or -1 on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_dispatch_background(
+    ptr: i64,
+    trigger: *const std::ffi::c_char,
+) -> i64 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let trigger = unsafe { CStr::from_ptr(trigger) }.to_string_lossy();
+    match ctx.wasm_runtime.dispatch_background(&trigger) {
+        Ok(job) => job,
+        Err(e) => {
+            ctx.push_error(e);
+            -1
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// Delivers queued bus messages, then advances the extension scheduler to `now`, in
+/// milliseconds of the caller's clock, and runs the scheduled callbacks that are due, all on
+/// the calling thread. Returns 0 on success, or 1 if any delivery or callback failed, with
+/// one error pushed per failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_tick(ptr: i64, now: i64) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let errors = ctx.wasm_runtime.tick(now.max(0) as u64);
+    if errors.is_empty() {
+        return 0;
+    }
+    for e in errors {
+        ctx.push_error(e);
+    }
+    1
+}
+
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// Returns 0 while `job` is running, 1 once it completed and 2 if it failed (the error is
+/// pushed to the error stack), or -1 for an unknown job. Finished jobs are forgotten once
+/// polled.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_poll_job(ptr: i64, job: i64) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    match ctx.wasm_runtime.jobs.poll(job) {
+        Some(JobStatus::Failed(e)) => {
+            ctx.push_error(e);
+            JOB_FAILED
+        }
+        Some(status) => status.code(),
+        None => -1,
+    }
+}
+
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// `callback`, if not null, must stay valid and be safe to call from any thread; it is
+/// invoked on a worker thread with the job id and status code whenever a job finishes.
+/// Pass null to remove the callback.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_set_job_callback(ptr: i64, callback: Option<JobCallback>) {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    ctx.wasm_runtime.jobs.set_callback(callback);
+}
+
 /// # Safety
 /// `ptr` must be a pointer previously returned by `ark_create_native_context`.
 /// `id` must be a valid C string. Returns 0 on success, 1 on failure.
@@ -208,6 +350,28 @@ pub unsafe extern "C" fn ark_disable_extension(ptr: i64, id: *const std::ffi::c_
     }
 }
 
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// `id` and `file_name` must be valid C strings. Returns 0 on success, 1 on failure; on
+/// failure the previously loaded version stays active.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_upgrade_extension(
+    ptr: i64,
+    id: *const std::ffi::c_char,
+    file_name: *const std::ffi::c_char,
+) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let id = unsafe { CStr::from_ptr(id) }.to_string_lossy();
+    let file_name = unsafe { CStr::from_ptr(file_name) }.to_string_lossy();
+    match ctx.wasm_runtime.upgrade_extension(&id, &file_name) {
+        Ok(_) => 0,
+        Err(e) => {
+            ctx.push_error(e);
+            1
+        }
+    }
+}
+
 /// # Safety
 /// `ptr` must be a pointer previously returned by `ark_create_native_context`.
 /// `id` must be a valid C string. Returns 0 on success, 1 on failure.
@@ -251,7 +415,7 @@ pub unsafe extern "C" fn ark_error_count(ptr: i64) -> i32 {
 }
 
 /// # Safety
-/// `ptr` must be a string previously returned by `ark_pop_error`, or null (no-op).
+/// `ptr` must be a string previously returned by an `ark_*` function, or null (no-op).
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn ark_free_string(ptr: *mut std::ffi::c_char) {
     if !ptr.is_null() {
@@ -277,7 +441,9 @@ pub unsafe extern "C" fn ark_set_enabled_vulkan_features(
         match serde_json::from_str(&json_str) {
             Ok(v) => v,
             Err(e) => {
-                ctx.push_error(anyhow::anyhow!("Failed to parse enabled vulkan features JSON: {e}"));
+                ctx.push_error(anyhow::anyhow!(
+                    "Failed to parse enabled vulkan features JSON: {e}"
+                ));
                 return 1;
             }
         }
@@ -289,7 +455,9 @@ pub unsafe extern "C" fn ark_set_enabled_vulkan_features(
             0
         }
         Err(e) => {
-            ctx.push_error(anyhow::anyhow!("Failed to lock enabled_vulkan_features: {e}"));
+            ctx.push_error(anyhow::anyhow!(
+                "Failed to lock enabled_vulkan_features: {e}"
+            ));
             1
         }
     }
@@ -313,7 +481,9 @@ pub unsafe extern "C" fn ark_set_enabled_vulkan_extensions(
         match serde_json::from_str(&json_str) {
             Ok(v) => v,
             Err(e) => {
-                ctx.push_error(anyhow::anyhow!("Failed to parse enabled vulkan extensions JSON: {e}"));
+                ctx.push_error(anyhow::anyhow!(
+                    "Failed to parse enabled vulkan extensions JSON: {e}"
+                ));
                 return 1;
             }
         }
@@ -325,8 +495,284 @@ pub unsafe extern "C" fn ark_set_enabled_vulkan_extensions(
             0
         }
         Err(e) => {
-            ctx.push_error(anyhow::anyhow!("Failed to lock enabled_vulkan_extensions: {e}"));
+            ctx.push_error(anyhow::anyhow!(
+                "Failed to lock enabled_vulkan_extensions: {e}"
+            ));
+            1
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// `locale` must be a valid C string such as `en_us` or `zh-CN`, or null to reset to `en_us`.
+/// The locale selects the `lang/<locale>.json` strings used for names, descriptions and errors.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_set_locale(ptr: i64, locale: *const std::ffi::c_char) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let locale = if locale.is_null() {
+        extension::i18n::DEFAULT_LOCALE.to_string()
+    } else {
+        extension::i18n::normalize_locale(&unsafe { CStr::from_ptr(locale) }.to_string_lossy())
+    };
+    match ctx.wasm_runtime.locale.lock() {
+        Ok(mut current) => {
+            *current = locale;
+            0
+        }
+        Err(e) => {
+            ctx.push_error(anyhow::anyhow!("Failed to lock locale: {e}"));
             1
         }
     }
 }
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// Returns a heap-allocated C string holding a JSON array describing the loaded extensions,
+/// with names and descriptions in the current locale, or null on failure (use `ark_pop_error`).
+/// The caller must free the string via `ark_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_list_extensions(ptr: i64) -> *mut std::ffi::c_char {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    match serde_json::to_string(&ctx.wasm_runtime.list_extensions()) {
+        Ok(json) => CString::new(json)
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            ctx.push_error(anyhow::anyhow!("Failed to serialize extension list: {e}"));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// `id` must be a valid C string. Starts recording a guest profile of the extension; profiling
+/// must be enabled in the host config. Returns 0 on success, 1 on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_start_profiling(ptr: i64, id: *const std::ffi::c_char) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let id = unsafe { CStr::from_ptr(id) }.to_string_lossy();
+    match ctx.wasm_runtime.start_profiling(&id) {
+        Ok(_) => 0,
+        Err(e) => {
+            ctx.push_error(e);
+            1
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a pointer previously returned by `ark_create_native_context`.
+/// `id` must be a valid C string. Stops profiling the extension and writes the profile in the
+/// Firefox profiler format. Returns a heap-allocated C string holding the file's path, or
+/// null on failure (use `ark_pop_error`). The caller must free the string via
+/// `ark_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_stop_profiling(
+    ptr: i64,
+    id: *const std::ffi::c_char,
+) -> *mut std::ffi::c_char {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let id = unsafe { CStr::from_ptr(id) }.to_string_lossy();
+    match ctx.wasm_runtime.stop_profiling(&id) {
+        Ok(path) => CString::new(path.to_string_lossy().into_owned())
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            ctx.push_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// Returns a heap-allocated C string holding a JSON array with the call metrics of every loaded
+/// extension, or null on failure (use `ark_pop_error`). The caller must free the string via
+/// `ark_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_get_extension_metrics(ptr: i64) -> *mut std::ffi::c_char {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    match serde_json::to_string(&ctx.wasm_runtime.extension_metrics()) {
+        Ok(json) => CString::new(json)
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            ctx.push_error(anyhow::anyhow!(
+                "Failed to serialize extension metrics: {e}"
+            ));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// Returns a heap-allocated C string holding a JSON object with the extensions skipped because
+/// the process crashed while they were initializing: `all` is true when every extension is
+/// skipped, and `suspects` lists the ones that were initializing. Returns null on failure (use
+/// `ark_pop_error`). The caller must free the string via `ark_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_get_skipped_extensions(ptr: i64) -> *mut std::ffi::c_char {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    match serde_json::to_string(&ctx.wasm_runtime.skipped_extensions()) {
+        Ok(json) => CString::new(json)
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            ctx.push_error(anyhow::anyhow!(
+                "Failed to serialize skipped extensions: {e}"
+            ));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// `id` must be a valid C string, or null to re-enable every skipped extension. Lets an
+/// extension skipped in safe mode load again. Returns 0 on success, 1 on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_reenable_extension(ptr: i64, id: *const std::ffi::c_char) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let id = if id.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(id) }.to_string_lossy().into_owned())
+    };
+    match ctx.wasm_runtime.reenable_extension(id.as_deref()) {
+        Ok(_) => 0,
+        Err(e) => {
+            ctx.push_error(e);
+            1
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// Returns a heap-allocated C string holding a JSON array summarizing the crash reports
+/// written for failed extension calls, newest first, with the `path` of each report file, or
+/// null on failure (use `ark_pop_error`). The caller must free the string via
+/// `ark_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_list_crash_reports(ptr: i64) -> *mut std::ffi::c_char {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let json = ctx
+        .wasm_runtime
+        .crash_reports()
+        .and_then(|reports| Ok(serde_json::to_string(&reports)?));
+    match json {
+        Ok(json) => CString::new(json)
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            ctx.push_error(e.context("Failed to list crash reports"));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// `id` must be a valid C string, or null to reset the metrics of every loaded extension.
+/// Returns 0 on success, 1 on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_reset_extension_metrics(ptr: i64, id: *const std::ffi::c_char) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let id = if id.is_null() {
+        None
+    } else {
+        Some(unsafe { CStr::from_ptr(id) }.to_string_lossy().into_owned())
+    };
+    match ctx.wasm_runtime.reset_metrics(id.as_deref()) {
+        Ok(_) => 0,
+        Err(e) => {
+            ctx.push_error(e);
+            1
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// Starts watching the extension folder for package changes when `enabled` is non-zero and
+/// stops otherwise. Returns 0 on success, 1 on failure.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_watch_extensions(ptr: i64, enabled: i32) -> i32 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    match ctx.wasm_runtime.watch_extensions(enabled != 0) {
+        Ok(_) => 0,
+        Err(e) => {
+            ctx.push_error(e);
+            1
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// Reloads the extensions whose packages changed since the last call and returns a
+/// heap-allocated C string holding a JSON array of reload events, or null on failure
+/// (use `ark_pop_error`). Must be called from the thread that calls into extensions.
+/// The caller must free the string via `ark_free_string`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_poll_extension_reloads(ptr: i64) -> *mut std::ffi::c_char {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    match serde_json::to_string(&ctx.wasm_runtime.process_package_changes()) {
+        Ok(json) => CString::new(json)
+            .map(CString::into_raw)
+            .unwrap_or(std::ptr::null_mut()),
+        Err(e) => {
+            ctx.push_error(anyhow::anyhow!("Failed to serialize reload events: {e}"));
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a valid pointer returned by `ark_create_native_context`.
+/// `id` must be a valid C string; `out_width` and `out_height` must be valid, writable pointers.
+/// Decodes the extension's PNG icon, scaled to fit a `size`x`size` square (0 or a negative size
+/// keeps the original size, sizes above 1024 are rejected), and returns its RGBA bytes,
+/// `width * height * 4` long, or null on failure (use `ark_pop_error`). The caller must free
+/// the buffer via `ark_free_bytes`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_get_extension_icon(
+    ptr: i64,
+    id: *const std::ffi::c_char,
+    size: i32,
+    out_width: *mut i32,
+    out_height: *mut i32,
+) -> *mut u8 {
+    let ctx = unsafe { &mut *(ptr as *mut NativeContext) };
+    let id = unsafe { CStr::from_ptr(id) }.to_string_lossy();
+    match ctx
+        .wasm_runtime
+        .extension_icon(&id, u32::try_from(size).unwrap_or(0))
+    {
+        Ok(icon) => {
+            unsafe {
+                *out_width = icon.width as i32;
+                *out_height = icon.height as i32;
+            }
+            Box::into_raw(icon.rgba.into_boxed_slice()) as *mut u8
+        }
+        Err(e) => {
+            ctx.push_error(e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` must be a buffer previously returned by an `ark_*` function together with its
+/// length `len`, or null (no-op).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ark_free_bytes(ptr: *mut u8, len: i64) {
+    if !ptr.is_null() {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len as usize)) });
+    }
+}
@@ -1,4 +1,4 @@
-use vulkanalia::{vk, Entry};
+use vulkanalia::{Entry, vk};
 use vulkanalia_vma::vma::VmaAllocator;
 
 #[derive(Debug, Clone)]